@@ -0,0 +1,142 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{RawFd, Socket, SocketError};
+use std::os::fd::AsRawFd;
+
+/// Default capacity of a `BufWriter`'s buffer.
+const DEFAULT_CAPACITY: usize = 8192;
+
+/// A socket whose writes are collected in a buffer and sent in large
+/// chunks, so emitting a message piece by piece costs a few syscalls
+/// rather than one per piece.
+///
+/// Data goes out when the buffer fills, on `flush`, and when the writer is
+/// dropped. Errors from the flush on drop can't be reported and are
+/// ignored, and the unsent data is lost with them; call `flush` (or
+/// `into_inner`) first to find out whether everything was sent.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{BufWriter, Socket};
+///
+/// let (client, server) = Socket::tcp_loopback_pair().unwrap();
+/// let mut out = BufWriter::new(client);
+/// for field in ["HTTP/1.1 200 OK\r\n", "Content-Length: 2\r\n", "\r\n", "ok"] {
+///     out.write_all(field.as_bytes()).unwrap();
+/// }
+/// assert_eq!(out.buffer().len(), 40);
+/// out.flush().unwrap();
+/// assert!(out.buffer().is_empty());
+///
+/// let mut buf = [0u8; 40];
+/// server.read_exact(&mut buf).unwrap();
+/// assert!(buf.ends_with(b"\r\n\r\nok"));
+/// ```
+pub struct BufWriter {
+    // Only None once into_inner has taken it.
+    sock: Option<Socket>,
+    buf: Vec<u8>,
+}
+
+impl BufWriter {
+    /// Buffer writes to `sock` in an 8 KiB buffer.
+    pub fn new(sock: Socket) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, sock)
+    }
+
+    /// Buffer writes to `sock` in a buffer of `capacity` bytes.
+    pub fn with_capacity(capacity: usize, sock: Socket) -> Self {
+        Self {
+            sock: Some(sock),
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Queue all of `buffer`, flushing first if it doesn't fit. Data at
+    /// least as large as the whole buffer is written straight through.
+    pub fn write_all(&mut self, buffer: &[u8]) -> Result<(), SocketError> {
+        if self.buf.len() + buffer.len() > self.buf.capacity() {
+            self.flush()?;
+        }
+        if buffer.len() >= self.buf.capacity() {
+            self.get_ref().write_all(buffer)
+        } else {
+            self.buf.extend_from_slice(buffer);
+            Ok(())
+        }
+    }
+
+    /// Send everything buffered so far. On error, whatever wasn't sent
+    /// stays buffered.
+    pub fn flush(&mut self) -> Result<(), SocketError> {
+        let sock = self.sock.as_ref().expect("BufWriter used after into_inner");
+        let mut done = 0;
+        let ret = loop {
+            if done == self.buf.len() {
+                break Ok(());
+            }
+            match sock.write(&self.buf[done..]) {
+                Ok(n) => done += n as usize,
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..done);
+        ret
+    }
+
+    /// The data written but not yet sent.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// The underlying socket, for reading replies. Writing to it directly
+    /// skips past anything still buffered.
+    pub fn get_ref(&self) -> &Socket {
+        self.sock.as_ref().expect("BufWriter used after into_inner")
+    }
+
+    /// Flush, then give back the underlying socket. If the flush fails,
+    /// the error is returned and the writer (with its unsent data) is
+    /// dropped.
+    pub fn into_inner(mut self) -> Result<Socket, SocketError> {
+        self.flush()?;
+        Ok(self.sock.take().expect("BufWriter used after into_inner"))
+    }
+}
+
+impl Drop for BufWriter {
+    fn drop(&mut self) {
+        if self.sock.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
+
+impl AsRawFd for BufWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.get_ref().as_raw_fd()
+    }
+}
@@ -0,0 +1,90 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+//! The Internet checksum (RFC 1071), for raw-socket users building IPv4,
+//! ICMP, UDP or TCP headers themselves.
+//!
+//! The checksum is the one's complement of the one's complement sum of
+//! the data taken as big-endian 16-bit words, with an odd trailing byte
+//! padded with zero. Write it into the packet with `to_be_bytes`.
+
+/// Compute the Internet checksum of `data`. Fill in a header's checksum
+/// field by zeroing it first and storing the result there.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::checksum::ip_checksum;
+///
+/// // The sample from RFC 1071 section 3 sums to 0xddf2.
+/// assert_eq!(ip_checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]), !0xddf2);
+///
+/// // An IPv4 header with its checksum field (bytes 10 and 11) zeroed.
+/// let mut header = [
+///     0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+///     0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+/// ];
+/// let sum = ip_checksum(&header);
+/// assert_eq!(sum, 0xb861);
+/// header[10..12].copy_from_slice(&sum.to_be_bytes());
+/// ```
+pub fn ip_checksum(data: &[u8]) -> u16 {
+    !fold(data)
+}
+
+/// Check data that carries its own checksum: the sum over all of it,
+/// checksum field included, comes out as all ones.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::checksum::verify_checksum;
+///
+/// let mut header = [
+///     0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+///     0xb8, 0x61, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+/// ];
+/// assert!(verify_checksum(&header));
+/// header[8] -= 1; // TTL decremented without fixing the checksum
+/// assert!(!verify_checksum(&header));
+/// ```
+pub fn verify_checksum(data: &[u8]) -> bool {
+    fold(data) == 0xffff
+}
+
+/// The one's complement sum of `data` as 16-bit big-endian words.
+fn fold(data: &[u8]) -> u16 {
+    let mut sum: u64 = 0;
+    let mut words = data.chunks_exact(2);
+    for w in &mut words {
+        sum += u16::from_be_bytes([w[0], w[1]]) as u64;
+    }
+    if let [last] = words.remainder() {
+        sum += (*last as u64) << 8;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
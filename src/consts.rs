@@ -0,0 +1,169 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+// Constant values as defined by Linux
+
+use std::ffi::c_int;
+
+pub(crate) const SOL_SOCKET: c_int = 1;
+pub(crate) const IPPROTO_TCP: c_int = 6;
+pub(crate) const SOL_IP: c_int = 0;
+pub(crate) const SOL_IPV6: c_int = 41;
+
+pub(crate) const IP_TTL: c_int = 2;
+pub(crate) const IPV6_UNICAST_HOPS: c_int = 16;
+pub(crate) const IP_MULTICAST_IF: c_int = 32;
+pub(crate) const IPV6_MULTICAST_IF: c_int = 17;
+
+#[cfg(target_os = "linux")]
+pub(crate) const IP_MTU_DISCOVER: c_int = 10;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_RECVERR: c_int = 11;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_RECVTTL: c_int = 12;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_MTU: c_int = 14;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_FREEBIND: c_int = 15;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_TRANSPARENT: c_int = 19;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_ADD_SOURCE_MEMBERSHIP: c_int = 39;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_DROP_SOURCE_MEMBERSHIP: c_int = 40;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_MTU_DISCOVER: c_int = 23;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_MTU: c_int = 24;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_RECVERR: c_int = 25;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_FLOWINFO_SEND: c_int = 33;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_RECVPKTINFO: c_int = 49;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_PKTINFO: c_int = 50;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_RECVHOPLIMIT: c_int = 51;
+#[cfg(target_os = "linux")]
+pub(crate) const IPV6_HOPLIMIT: c_int = 52;
+// IP_MTU_DISCOVER and IPV6_MTU_DISCOVER values
+#[cfg(target_os = "linux")]
+pub(crate) const IP_PMTUDISC_DONT: c_int = 0;
+#[cfg(target_os = "linux")]
+pub(crate) const IP_PMTUDISC_DO: c_int = 2;
+// Netfilter conntrack lookups, the same number at SOL_IP and SOL_IPV6
+#[cfg(target_os = "linux")]
+pub(crate) const SO_ORIGINAL_DST: c_int = 80;
+
+pub(crate) const SO_REUSEADDR: c_int = 2;
+pub(crate) const SO_TYPE: c_int = 3;
+pub(crate) const SO_ERROR: c_int = 4;
+pub(crate) const SO_DONTROUTE: c_int = 5;
+pub(crate) const SO_SNDBUF: c_int = 7;
+pub(crate) const SO_RCVBUF: c_int = 8;
+pub(crate) const SO_KEEPALIVE: c_int = 9;
+pub(crate) const SO_LINGER: c_int = 13;
+#[cfg(target_os = "linux")]
+pub(crate) const SO_REUSEPORT: c_int = 15;
+#[cfg(target_os = "linux")]
+pub(crate) const SO_PASSCRED: c_int = 16;
+#[cfg(target_os = "linux")]
+pub(crate) const SO_PRIORITY: c_int = 12;
+pub(crate) const SO_RCVTIMEO: c_int = 20;
+pub(crate) const SO_SNDTIMEO: c_int = 21;
+#[cfg(target_os = "linux")]
+pub(crate) const SO_RCVBUFFORCE: c_int = 33;
+#[cfg(target_os = "linux")]
+pub(crate) const SO_MARK: c_int = 36;
+// OpenBSD uses its own socket level, so both are needed for SO_RTABLE
+#[cfg(target_os = "openbsd")]
+pub(crate) const OPENBSD_SOL_SOCKET: c_int = 0xffff;
+#[cfg(target_os = "openbsd")]
+pub(crate) const SO_RTABLE: c_int = 0x1021;
+pub(crate) const SO_PROTOCOL: c_int = 38;
+pub(crate) const SO_DOMAIN: c_int = 39;
+
+pub(crate) const TCP_NODELAY: c_int = 1;
+pub(crate) const TCP_MAXSEG: c_int = 2;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_CORK: c_int = 3;
+#[cfg(not(target_os = "linux"))]
+pub(crate) const TCP_NOPUSH: c_int = 4;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_KEEPIDLE: c_int = 4;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_SYNCNT: c_int = 7;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_DEFER_ACCEPT: c_int = 9;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_INFO: c_int = 11;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_FASTOPEN: c_int = 23;
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_CONGESTION: c_int = 13;
+// Longest congestion control name, including the NUL
+#[cfg(target_os = "linux")]
+pub(crate) const TCP_CA_NAME_MAX: usize = 16;
+
+pub(crate) const F_GETFD: c_int = 1;
+pub(crate) const F_SETFD: c_int = 2;
+pub(crate) const FD_CLOEXEC: c_int = 1;
+pub(crate) const F_GETFL: c_int = 3;
+pub(crate) const F_SETFL: c_int = 4;
+pub(crate) const O_NONBLOCK: c_int = 0o4000;
+
+pub(crate) const SOMAXCONN: c_int = 4096;
+
+// Control message carrying file descriptors, at SOL_SOCKET
+pub(crate) const SCM_RIGHTS: c_int = 1;
+#[cfg(target_os = "linux")]
+pub(crate) const SCM_CREDENTIALS: c_int = 2;
+// Mark fds received with SCM_RIGHTS close-on-exec
+#[cfg(target_os = "linux")]
+pub(crate) const MSG_CMSG_CLOEXEC: c_int = 0x40000000;
+// recvmmsg: block for the first datagram only
+#[cfg(target_os = "linux")]
+pub(crate) const MSG_WAITFORONE: c_int = 0x10000;
+
+// First fd passed by systemd socket activation
+pub(crate) const SD_LISTEN_FDS_START: c_int = 3;
+
+#[cfg(target_os = "linux")]
+pub(crate) const TIOCOUTQ: u64 = 0x5411;
+// Also known as FIONREAD
+#[cfg(target_os = "linux")]
+pub(crate) const SIOCINQ: u64 = 0x541b;
+#[cfg(target_os = "linux")]
+pub(crate) const SIOCATMARK: u64 = 0x8905;
+#[cfg(target_os = "linux")]
+pub(crate) const SIOCGIFMTU: u64 = 0x8921;
+
+pub(crate) const POLLIN: i16 = 0x1;
+pub(crate) const POLLOUT: i16 = 0x4;
+
+pub(crate) const SIGPIPE: c_int = 13;
+pub(crate) const SIG_DFL: usize = 0;
+pub(crate) const SIG_IGN: usize = 1;
+pub(crate) const SIG_ERR: usize = usize::MAX;
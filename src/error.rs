@@ -0,0 +1,138 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use std::fmt;
+
+// errno values as defined by Linux
+pub(crate) const EPERM: i32 = 1;
+#[cfg(target_os = "linux")]
+pub(crate) const ENOENT: i32 = 2;
+pub(crate) const EINTR: i32 = 4;
+pub(crate) const EAGAIN: i32 = 11;
+pub(crate) const EACCES: i32 = 13;
+#[cfg(feature = "test-util")]
+pub(crate) const EPIPE: i32 = 32;
+pub(crate) const EINVAL: i32 = 22;
+pub(crate) const ENFILE: i32 = 23;
+pub(crate) const EMFILE: i32 = 24;
+pub(crate) const EMSGSIZE: i32 = 90;
+pub(crate) const ENOPROTOOPT: i32 = 92;
+pub(crate) const EPROTONOSUPPORT: i32 = 93;
+pub(crate) const ESOCKTNOSUPPORT: i32 = 94;
+pub(crate) const EAFNOSUPPORT: i32 = 97;
+pub(crate) const EADDRINUSE: i32 = 98;
+#[cfg(target_os = "linux")]
+pub(crate) const EADDRNOTAVAIL: i32 = 99;
+pub(crate) const ECONNABORTED: i32 = 103;
+pub(crate) const ECONNRESET: i32 = 104;
+pub(crate) const EISCONN: i32 = 106;
+pub(crate) const ENOTCONN: i32 = 107;
+pub(crate) const ETIMEDOUT: i32 = 110;
+pub(crate) const ECONNREFUSED: i32 = 111;
+pub(crate) const EINPROGRESS: i32 = 115;
+
+// getaddrinfo status for "look at errno instead"
+pub(crate) const EAI_SYSTEM: i32 = -11;
+
+/// Errors returned by the socket operations.
+///
+/// Conditions callers commonly want to handle are given their own
+/// variant. Everything else is reported as `Other` with the raw errno.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SocketError {
+    PermissionDenied,
+    WouldBlock,
+    Interrupted,
+    TimedOut,
+    /// A bounded write ran out of time after writing this many bytes.
+    WriteTimedOut(usize),
+    ConnectionRefused,
+    /// The peer aborted the connection with a reset, as opposed to the
+    /// clean close a 0-byte read reports.
+    ConnectionReset,
+    /// `connect` on a stream socket that is already connected, including
+    /// one returned by `accept`.
+    AlreadyConnected,
+    AddrInUse,
+    /// The peer closed the connection before the requested data arrived.
+    UnexpectedEof,
+    /// A message was larger than the caller or the protocol allows.
+    MessageTooLarge,
+    /// The option, protocol or socket type isn't available on this
+    /// system, e.g. because a kernel module isn't loaded.
+    Unsupported,
+    /// The operation was abandoned because its cancellation fd fired.
+    Cancelled,
+    /// Name resolution failed with the given `EAI_*` code.
+    Resolve(i32),
+    Other(i32),
+}
+
+impl SocketError {
+    /// Map a raw errno value onto a `SocketError`.
+    pub const fn from_errno(errno: i32) -> Self {
+        match errno {
+            EPERM | EACCES => Self::PermissionDenied,
+            EAGAIN => Self::WouldBlock,
+            EINTR => Self::Interrupted,
+            EMSGSIZE => Self::MessageTooLarge,
+            ETIMEDOUT => Self::TimedOut,
+            ECONNREFUSED => Self::ConnectionRefused,
+            ECONNRESET => Self::ConnectionReset,
+            EISCONN => Self::AlreadyConnected,
+            EADDRINUSE => Self::AddrInUse,
+            ENOPROTOOPT | EPROTONOSUPPORT | ESOCKTNOSUPPORT => Self::Unsupported,
+            e => Self::Other(e),
+        }
+    }
+
+    /// Build a `SocketError` from the calling thread's current errno.
+    pub fn last() -> Self {
+        Self::from_errno(std::io::Error::last_os_error().raw_os_error().unwrap_or(0))
+    }
+}
+
+impl fmt::Display for SocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PermissionDenied => write!(f, "permission denied"),
+            Self::WouldBlock => write!(f, "operation would block"),
+            Self::Interrupted => write!(f, "interrupted by a signal"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::WriteTimedOut(n) => write!(f, "timed out after writing {} bytes", n),
+            Self::ConnectionRefused => write!(f, "connection refused"),
+            Self::ConnectionReset => write!(f, "connection reset by peer"),
+            Self::AlreadyConnected => write!(f, "socket is already connected"),
+            Self::AddrInUse => write!(f, "address already in use"),
+            Self::UnexpectedEof => write!(f, "connection closed before all data was read"),
+            Self::MessageTooLarge => write!(f, "message too large"),
+            Self::Unsupported => write!(f, "not supported on this system"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Resolve(code) => write!(f, "{}", super::safe::safe_gai_strerror(*code)),
+            Self::Other(e) => write!(f, "{}", std::io::Error::from_raw_os_error(*e)),
+        }
+    }
+}
+
+impl std::error::Error for SocketError {}
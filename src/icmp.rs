@@ -0,0 +1,154 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+//! ICMP echo (ping) over IPv4 sockets.
+//!
+//! ICMP over IPv4 carries a plain Internet checksum of the ICMP message,
+//! which `echo_request` fills in. Raw sockets receive replies with the
+//! IPv4 header in front, ping sockets without it; `parse_echo_reply`
+//! accepts both. See `icmp6` for the IPv6 side.
+
+use super::checksum::ip_checksum;
+use super::{AddressFamily, BindFamily, IpProto, MsgFlags, Socket, SocketError, SocketType};
+use std::time::{Duration, Instant};
+
+pub use super::icmp6::EchoReply;
+
+pub const ECHO_REQUEST: u8 = 8;
+pub const ECHO_REPLY: u8 = 0;
+
+/// Build an ICMP echo request message, checksum included.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::checksum::verify_checksum;
+/// use mzsocket::icmp;
+///
+/// let msg = icmp::echo_request(7, 1, b"hi");
+/// assert_eq!(msg[0], icmp::ECHO_REQUEST);
+/// assert!(verify_checksum(&msg));
+/// ```
+pub fn echo_request(id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut msg = vec![ECHO_REQUEST, 0, 0, 0];
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&seq.to_be_bytes());
+    msg.extend_from_slice(payload);
+    let sum = ip_checksum(&msg);
+    msg[2..4].copy_from_slice(&sum.to_be_bytes());
+    msg
+}
+
+/// Parse an ICMP message, with or without the IPv4 header in front,
+/// returning `None` unless it is an echo reply.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::icmp;
+///
+/// let mut msg = icmp::echo_request(7, 1, b"hi");
+/// msg[0] = icmp::ECHO_REPLY;
+/// assert_eq!(icmp::parse_echo_reply(&msg).unwrap().seq, 1);
+///
+/// // The same behind a minimal IPv4 header, as a raw socket sees it.
+/// let mut packet = vec![0x45; 1];
+/// packet.resize(20, 0);
+/// packet.extend_from_slice(&msg);
+/// assert_eq!(icmp::parse_echo_reply(&packet).unwrap().payload, b"hi");
+/// assert!(icmp::parse_echo_reply(&icmp::echo_request(7, 1, b"hi")).is_none());
+/// ```
+pub fn parse_echo_reply(msg: &[u8]) -> Option<EchoReply> {
+    let msg = match msg.first() {
+        // An IPv4 header: version 4, length in 32-bit words.
+        Some(&b) if b >> 4 == 4 => msg.get((b & 0x0f) as usize * 4..)?,
+        _ => msg,
+    };
+    if msg.len() < 8 || msg[0] != ECHO_REPLY || msg[1] != 0 {
+        return None;
+    }
+    Some(EchoReply {
+        id: u16::from_be_bytes([msg[4], msg[5]]),
+        seq: u16::from_be_bytes([msg[6], msg[7]]),
+        payload: msg[8..].to_vec(),
+    })
+}
+
+/// Send one echo request to `addr` (in host byte order, as from
+/// `inet_addr`) and wait up to `timeout` for the reply, returning the
+/// round trip time.
+///
+/// As with `icmp6::ping`, an unprivileged ping socket (`SOCK_DGRAM`,
+/// allowed by `net.ipv4.ping_group_range`) is tried before a raw socket,
+/// which needs `CAP_NET_RAW`, and replies are matched on the sequence
+/// number and payload.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{icmp, inet_addr, SocketError};
+/// use std::time::Duration;
+///
+/// match icmp::ping(inet_addr("127.0.0.1").unwrap(), Duration::from_secs(1)) {
+///     Ok(rtt) => println!("127.0.0.1 answered in {:?}", rtt),
+///     // Neither ping sockets nor raw sockets are allowed for this user.
+///     Err(SocketError::PermissionDenied) => {}
+///     Err(e) => panic!("ping failed: {}", e),
+/// }
+/// ```
+pub fn ping(addr: u32, timeout: Duration) -> Result<Duration, SocketError> {
+    let mut sock = Socket::create(AddressFamily::Inet, SocketType::DataGram, IpProto::Icmp)
+        .or_else(|_| Socket::create(AddressFamily::Inet, SocketType::Raw, IpProto::Icmp))?;
+    let seq = std::process::id() as u16;
+    let payload = b"mzsocket";
+    let start = Instant::now();
+    sock.sendto(&echo_request(seq, seq, payload), &BindFamily::Inet(addr, 0))?;
+    await_reply(&mut sock, start + timeout, |msg| {
+        parse_echo_reply(msg).is_some_and(|r| r.seq == seq && r.payload == payload)
+    })?;
+    Ok(start.elapsed())
+}
+
+/// Receive on `sock` until `is_reply` accepts a message, giving up with
+/// `SocketError::TimedOut` at `deadline`. Signals don't end the wait.
+pub(crate) fn await_reply(
+    sock: &mut Socket,
+    deadline: Instant,
+    is_reply: impl Fn(&[u8]) -> bool,
+) -> Result<(), SocketError> {
+    let mut buf = [0u8; 1500];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(SocketError::TimedOut);
+        }
+        sock.set_read_timeout(Some(left))?;
+        match sock.recvfrom(&mut buf, MsgFlags::NONE) {
+            Ok((len, _)) if is_reply(&buf[..len as usize]) => return Ok(()),
+            Ok(_) | Err(SocketError::Interrupted) => {}
+            Err(SocketError::WouldBlock) | Err(SocketError::TimedOut) => return Err(SocketError::TimedOut),
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+//! ICMPv6 echo (ping) over IPv6 sockets.
+//!
+//! Unlike ICMP over IPv4, the ICMPv6 checksum covers a pseudo-header with
+//! the source and destination addresses, which userspace does not know
+//! for sure before routing. Linux therefore always computes it for
+//! `IPPROTO_ICMPV6` sockets (RFC 3542), so `echo_request` leaves the
+//! checksum field zero. Received messages likewise start at the ICMPv6
+//! header, with no IPv6 header in front.
+
+use super::{AddressFamily, BindFamily, IpProto, Socket, SocketError, SocketType};
+use std::time::{Duration, Instant};
+
+pub const ECHO_REQUEST: u8 = 128;
+pub const ECHO_REPLY: u8 = 129;
+
+/// An echo reply as parsed by `parse_echo_reply`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EchoReply {
+    pub id: u16,
+    pub seq: u16,
+    pub payload: Vec<u8>,
+}
+
+/// Build an ICMPv6 echo request message. The checksum is left for the
+/// kernel to fill in.
+pub fn echo_request(id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut msg = vec![ECHO_REQUEST, 0, 0, 0];
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&seq.to_be_bytes());
+    msg.extend_from_slice(payload);
+    msg
+}
+
+/// Parse an ICMPv6 message, returning `None` unless it is an echo reply.
+pub fn parse_echo_reply(msg: &[u8]) -> Option<EchoReply> {
+    if msg.len() < 8 || msg[0] != ECHO_REPLY || msg[1] != 0 {
+        return None;
+    }
+    Some(EchoReply {
+        id: u16::from_be_bytes([msg[4], msg[5]]),
+        seq: u16::from_be_bytes([msg[6], msg[7]]),
+        payload: msg[8..].to_vec(),
+    })
+}
+
+/// Send one echo request to `addr` and wait up to `timeout` for the
+/// reply, returning the round trip time.
+///
+/// An unprivileged ping socket (`SOCK_DGRAM`) is tried first; it needs the
+/// caller's group in `net.ipv4.ping_group_range`, which also covers IPv6.
+/// Otherwise a raw socket is used, which needs `CAP_NET_RAW`. A ping
+/// socket replaces the identifier with its own, so replies are matched on
+/// the sequence number and payload.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{icmp6, inet6_addr, SocketError};
+/// use std::time::Duration;
+///
+/// match icmp6::ping(inet6_addr("::1").unwrap(), Duration::from_secs(1)) {
+///     Ok(rtt) => println!("::1 answered in {:?}", rtt),
+///     // Neither ping sockets nor raw sockets are allowed for this user.
+///     Err(SocketError::PermissionDenied) => {}
+///     Err(e) => panic!("ping failed: {}", e),
+/// }
+/// ```
+pub fn ping(addr: u128, timeout: Duration) -> Result<Duration, SocketError> {
+    let mut sock = Socket::create(AddressFamily::Inet6, SocketType::DataGram, IpProto::Icmpv6)
+        .or_else(|_| Socket::create(AddressFamily::Inet6, SocketType::Raw, IpProto::Icmpv6))?;
+    let seq = std::process::id() as u16;
+    let payload = b"mzsocket";
+    let start = Instant::now();
+    sock.sendto(&echo_request(seq, seq, payload), &BindFamily::Inet6(addr, 0))?;
+    super::icmp::await_reply(&mut sock, start + timeout, |msg| {
+        parse_echo_reply(msg).is_some_and(|r| r.seq == seq && r.payload == payload)
+    })?;
+    Ok(start.elapsed())
+}
@@ -22,20 +22,49 @@
 //! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 //! THE SOFTWARE.
 
+mod buffered;
+pub mod checksum;
+mod consts;
+mod error;
+pub mod icmp;
+pub mod icmp6;
+mod poll;
+mod reactor;
 mod safe;
+mod shared;
+mod socket_io;
+mod split;
 mod structs;
+mod take;
 
 use std::{
-    ffi::{c_int, c_longlong, c_uchar, c_uint, c_ulonglong, c_void},
+    ffi::{c_int, c_longlong, c_uchar, c_uint, c_ulonglong, c_void, CString},
     mem::size_of,
+    os::fd::{AsRawFd, FromRawFd, IntoRawFd},
+    time::{Duration, Instant},
 };
 
 extern "C" {
     fn listen(fd: c_int, backlog: c_int) -> c_int;
     fn accept(fd: c_int, s: *mut c_void, slen: *mut c_uint) -> c_int;
+    #[cfg(target_os = "linux")]
+    fn accept4(fd: c_int, s: *mut c_void, slen: *mut c_uint, flags: c_int) -> c_int;
     fn read(fd: c_int, buffer: *mut c_uchar, buflen: c_ulonglong) -> c_longlong;
     fn write(fd: c_int, buffer: *const c_uchar, buflen: c_ulonglong) -> c_longlong;
+    fn recv(fd: c_int, buffer: *mut c_uchar, buflen: c_ulonglong, flags: c_int) -> c_longlong;
+    fn send(fd: c_int, buffer: *const c_uchar, buflen: c_ulonglong, flags: c_int) -> c_longlong;
     fn fcntl(fd: c_int, cmd: c_int, val: c_int) -> c_int;
+    fn lseek(fd: c_int, offset: c_longlong, whence: c_int) -> c_longlong;
+    fn getpeername(fd: c_int, s: *mut c_void, slen: *mut c_uint) -> c_int;
+    fn getsockname(fd: c_int, s: *mut c_void, slen: *mut c_uint) -> c_int;
+    fn recvfrom(
+        fd: c_int,
+        buffer: *mut c_uchar,
+        buflen: c_ulonglong,
+        flags: c_int,
+        s: *mut c_void,
+        slen: *mut c_uint,
+    ) -> c_longlong;
 }
 
 // Re-exports
@@ -46,168 +75,3730 @@ pub type IpProto = structs::IpProto;
 pub type BindFamily = structs::BindFamily;
 pub type InetSockAddr = structs::InetSockAddr;
 pub type Inet6SockAddr = structs::Inet6SockAddr;
+pub type InetAddr = structs::InetAddr;
+pub type Inet6Addr = structs::Inet6Addr;
 pub type UnixSockAddr = structs::UnixSockAddr;
+pub type SockAddr = structs::SockAddr;
+pub type ConnectState = structs::ConnectState;
+pub type AcceptConfig = structs::AcceptConfig;
+pub type AcceptFlags = structs::AcceptFlags;
+pub type SocketOptions = structs::SocketOptions;
+pub type LinkAddr = structs::LinkAddr;
+pub type PacketType = structs::PacketType;
+pub type ErrOrigin = structs::ErrOrigin;
+pub type SockExtendedErr = structs::SockExtendedErr;
+pub type TcpInfo = structs::TcpInfo;
+pub type SigHandler = structs::SigHandler;
+pub type InterfaceAddr = structs::InterfaceAddr;
+pub type IfFlags = structs::IfFlags;
+pub type PeerCred = structs::PeerCred;
+pub const UNIX_PATH_LEN: usize = structs::UNIX_PATH_LEN;
+pub const INET_SOCKADDR_STRLEN: usize = structs::INET_SOCKADDR_STRLEN;
+pub const INET6_SOCKADDR_STRLEN: usize = structs::INET6_SOCKADDR_STRLEN;
+pub const IPV6_FLOWLABEL_MASK: u32 = structs::IPV6_FLOWLABEL_MASK;
+pub type PollFd<'a> = poll::PollFd<'a>;
+pub type PollEvents = poll::PollEvents;
+pub use poll::poll;
+pub type Interest = reactor::Interest;
+pub use reactor::Reactor;
+pub use socket_io::SocketIo;
+#[cfg(feature = "test-util")]
+pub type MockSocket = socket_io::MockSocket;
+pub type SocketError = error::SocketError;
+pub type RawFd = std::os::fd::RawFd;
+pub type Shutdown = structs::Shutdown;
+pub type MsgFlags = structs::MsgFlags;
+pub type ReadHalf = split::ReadHalf;
+pub type WriteHalf = split::WriteHalf;
+pub type SharedSocket = shared::SharedSocket;
+pub type TakeSocket = take::TakeSocket;
+pub type BufWriter = buffered::BufWriter;
 
+use structs::TimeVal;
+
+/// A BSD socket, closed when dropped.
+///
+/// Sockets are not seekable: they carry a stream of bytes (or datagrams)
+/// with no file offset, so `Socket` deliberately does not implement
+/// `std::io::Seek`. Offset-based file I/O habits don't carry over.
 pub struct Socket {
     fd: c_int,
     af: AddressFamily,
+    read_timeout: Option<Duration>,
+    restart_on_interrupt: bool,
+    accept_backoff: Duration,
+    guard_fd: Option<std::os::fd::OwnedFd>,
+    // errno fetched from SO_ERROR by has_error but not yet taken, or 0
+    pending_error: std::sync::atomic::AtomicI32,
+    // a connect_nonblocking returned InProgress and check_connect hasn't
+    // seen it finish yet
+    connect_pending: std::sync::atomic::AtomicBool,
+    #[cfg(feature = "stats")]
+    bytes_read: std::sync::atomic::AtomicU64,
+    #[cfg(feature = "stats")]
+    bytes_written: std::sync::atomic::AtomicU64,
 }
 
-impl Socket {
-    pub fn new(family: AddressFamily, st: SocketType, proto: Option<IpProto>) -> Result<Self, i32> {
-        let pr = proto.unwrap_or(IpProto::Ip);
-        let ws = safe::safe_socket(family, st, pr);
-        if ws < 0 {
-            Err(ws)
+impl Socket {
+    fn from_fd(fd: c_int, af: AddressFamily) -> Self {
+        Self {
+            fd,
+            af,
+            read_timeout: None,
+            restart_on_interrupt: true,
+            accept_backoff: Duration::from_millis(10),
+            guard_fd: None,
+            pending_error: std::sync::atomic::AtomicI32::new(0),
+            connect_pending: std::sync::atomic::AtomicBool::new(false),
+            #[cfg(feature = "stats")]
+            bytes_read: std::sync::atomic::AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            bytes_written: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    fn create(family: AddressFamily, st: SocketType, proto: IpProto) -> Result<Self, SocketError> {
+        let fd = safe::safe_socket(family, st, proto);
+        if fd < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(Self::from_fd(fd, family))
+        }
+    }
+
+    /// Create a socket. A `proto` of `None` lets the kernel pick the usual
+    /// protocol for `family` and `st`.
+    ///
+    /// A combination the system can't provide fails with
+    /// `SocketError::Unsupported` instead of handing back a socket that
+    /// doesn't work. On Linux, `SocketType::SeqPacket` is always available
+    /// for `AddressFamily::Unix`, but over IPv4 or IPv6 it only exists as
+    /// SCTP (`IpProto::Sctp`), which needs the `sctp` kernel module. This
+    /// crate has no SCTP-specific calls, so such a socket gets plain
+    /// one-to-one message semantics. See `Socket::supports`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketError, SocketType};
+    ///
+    /// assert!(Socket::new(AddressFamily::Unix, SocketType::SeqPacket, None).is_ok());
+    /// // SOCK_SEQPACKET over IP only exists as SCTP.
+    /// assert_eq!(
+    ///     Socket::new(AddressFamily::Inet, SocketType::SeqPacket, None).err(),
+    ///     Some(SocketError::Unsupported)
+    /// );
+    /// ```
+    pub fn new(family: AddressFamily, st: SocketType, proto: Option<IpProto>) -> Result<Self, SocketError> {
+        Self::create(family, st, proto.unwrap_or(IpProto::Ip))
+    }
+
+    /// Whether an IPv4 socket of type `st` with protocol `proto` can be
+    /// created here. The probe socket is closed straight away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{IpProto, Socket, SocketType};
+    ///
+    /// assert!(Socket::supports(SocketType::Stream, IpProto::Tcp));
+    /// if !Socket::supports(SocketType::SeqPacket, IpProto::Sctp) {
+    ///     println!("no SCTP on this host");
+    /// }
+    /// ```
+    pub fn supports(st: SocketType, proto: IpProto) -> bool {
+        Self::create(AddressFamily::Inet, st, proto).is_ok()
+    }
+
+    /// Take over a socket inherited from systemd socket activation or
+    /// inetd, already bound and usually listening. Nothing is re-bound.
+    ///
+    /// `fd` is checked to be a socket (`SO_TYPE`), failing with
+    /// `SocketError::Other(ENOTSOCK)` otherwise, and marked close-on-exec
+    /// so it isn't leaked into programs this one starts. The returned
+    /// `Socket` owns `fd` and closes it when dropped; on error `fd` is
+    /// left alone.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mzsocket::Socket;
+    ///
+    /// for fd in Socket::listen_fds() {
+    ///     let mut listener = Socket::from_activation(fd).unwrap();
+    ///     let (conn, _) = listener.accept().unwrap();
+    ///     conn.write_all(b"hello from a socket-activated service\n").unwrap();
+    /// }
+    /// ```
+    ///
+    /// Anything but a socket is refused:
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketError, SocketType};
+    /// use std::os::fd::{AsRawFd, IntoRawFd};
+    ///
+    /// let file = std::fs::File::open("/dev/null").unwrap();
+    /// // ENOTSOCK has no dedicated variant.
+    /// assert!(matches!(Socket::from_activation(file.as_raw_fd()), Err(SocketError::Other(_))));
+    ///
+    /// let fd = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap().into_raw_fd();
+    /// let sock = Socket::from_activation(fd).unwrap();
+    /// assert_eq!(sock.domain().unwrap(), AddressFamily::Inet);
+    /// ```
+    pub fn from_activation(fd: RawFd) -> Result<Socket, SocketError> {
+        let mut st: c_int = 0;
+        if safe::safe_getsockopt(fd, consts::SOL_SOCKET, consts::SO_TYPE, &mut st) < 0 {
+            return Err(SocketError::last());
+        }
+        let flags = unsafe { fcntl(fd, consts::F_GETFD, 0) };
+        if flags < 0 || unsafe { fcntl(fd, consts::F_SETFD, flags | consts::FD_CLOEXEC) } < 0 {
+            return Err(SocketError::last());
+        }
+        Ok(unsafe { Self::from_raw_fd(fd) })
+    }
+
+    /// The fds passed to this process by systemd socket activation, or an
+    /// empty list when there are none.
+    ///
+    /// Follows the `sd_listen_fds` protocol: `LISTEN_PID` must name this
+    /// process, and `LISTEN_FDS` says how many fds were passed, starting
+    /// at fd 3. The variables are left set, so a child process can tell
+    /// from `LISTEN_PID` that they aren't meant for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::Socket;
+    ///
+    /// assert!(Socket::listen_fds().is_empty());
+    /// std::env::set_var("LISTEN_PID", std::process::id().to_string());
+    /// std::env::set_var("LISTEN_FDS", "2");
+    /// assert_eq!(Socket::listen_fds(), vec![3, 4]);
+    /// ```
+    pub fn listen_fds() -> Vec<RawFd> {
+        let var = |name| std::env::var(name).ok().and_then(|v| v.parse::<u32>().ok());
+        match (var("LISTEN_PID"), var("LISTEN_FDS")) {
+            (Some(pid), Some(n)) if pid == std::process::id() => {
+                (consts::SD_LISTEN_FDS_START..).take(n as usize).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Bind the socket to a local address.
+    ///
+    /// Ports below 1024 are privileged. Binding one without
+    /// `CAP_NET_BIND_SERVICE` fails with `SocketError::PermissionDenied`.
+    /// Linux moves that boundary with the
+    /// `net.ipv4.ip_unprivileged_port_start` sysctl; containers often set
+    /// it to 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    ///
+    /// extern "C" {
+    ///     fn geteuid() -> u32;
+    /// }
+    ///
+    /// // root holds CAP_NET_BIND_SERVICE, and port 80 may not be
+    /// // privileged here at all.
+    /// let unprivileged_start = std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+    ///     .ok()
+    ///     .and_then(|s| s.trim().parse::<u16>().ok())
+    ///     .unwrap_or(1024);
+    /// let privileged = unsafe { geteuid() } != 0 && unprivileged_start > 80;
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// match sock.bind(BindFamily::Inet(0, 80)) {
+    ///     Err(SocketError::PermissionDenied) => assert!(privileged),
+    ///     Ok(()) | Err(SocketError::AddrInUse) => assert!(!privileged),
+    ///     Err(e) => panic!("{}", e),
+    /// }
+    /// ```
+    pub fn bind(&mut self, bf: BindFamily) -> Result<(), SocketError> {
+        if safe::safe_bind(self.fd, bf) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// What an event loop should wait for on this socket: writability
+    /// while a `connect_nonblocking` is still in progress, readability
+    /// otherwise (data to read, datagrams on a bound socket, or
+    /// connections to accept on a listener).
+    ///
+    /// Write readiness is only worth asking for after a write has returned
+    /// `SocketError::WouldBlock`, so it isn't included by default. See
+    /// `Reactor`.
+    ///
+    /// Asking doesn't consume a failed connect's error: `check_connect`
+    /// still reports it afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, poll, AddressFamily, BindFamily, Interest, PollEvents, PollFd, Socket, SocketError, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut probe = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// probe.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let port = probe.local_port().unwrap();
+    /// drop(probe);
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// let _ = sock.connect_nonblocking(BindFamily::Inet(lo, port));
+    /// poll(&mut [PollFd::new(&sock, PollEvents::OUT)], Some(Duration::from_secs(5))).unwrap();
+    /// assert_eq!(sock.readiness_interest(), Interest::WRITABLE);
+    /// assert_eq!(sock.check_connect(), Err(SocketError::ConnectionRefused));
+    /// assert_eq!(sock.readiness_interest(), Interest::READABLE);
+    ///
+    /// // A bound datagram socket has no peer, but waits for datagrams.
+    /// let mut udp = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// udp.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// assert_eq!(udp.readiness_interest(), Interest::READABLE);
+    /// ```
+    pub fn readiness_interest(&self) -> Interest {
+        let pending = self.connect_pending.load(std::sync::atomic::Ordering::Relaxed);
+        // Not check_connect: that would take a pending connect error out
+        // of SO_ERROR before the caller gets to see it.
+        if pending && self.peer_state() == Ok(ConnectState::InProgress) {
+            Interest::WRITABLE
+        } else {
+            Interest::READABLE
+        }
+    }
+
+    /// Switch the socket to non-blocking mode and register it with
+    /// `reactor` for its `readiness_interest`.
+    ///
+    /// # Examples
+    ///
+    /// An adapter over raw `epoll`:
+    ///
+    /// ```
+    /// use mzsocket::{Interest, RawFd, Reactor, Socket, SocketError, SocketType};
+    /// use std::os::fd::AsRawFd;
+    ///
+    /// #[repr(C, packed)]
+    /// struct EpollEvent {
+    ///     events: u32,
+    ///     data: u64,
+    /// }
+    ///
+    /// extern "C" {
+    ///     fn epoll_create1(flags: i32) -> i32;
+    ///     fn epoll_ctl(epfd: i32, op: i32, fd: i32, ev: *mut EpollEvent) -> i32;
+    ///     fn epoll_wait(epfd: i32, evs: *mut EpollEvent, max: i32, timeout: i32) -> i32;
+    /// }
+    ///
+    /// const EPOLLIN: u32 = 0x1;
+    /// const EPOLLOUT: u32 = 0x4;
+    /// const EPOLL_CTL_ADD: i32 = 1;
+    /// const EPOLL_CTL_DEL: i32 = 2;
+    ///
+    /// struct Epoll(i32);
+    ///
+    /// impl Reactor for Epoll {
+    ///     type Token = u64;
+    ///
+    ///     fn register(&mut self, fd: RawFd, interest: Interest) -> Result<u64, SocketError> {
+    ///         let mut events = 0;
+    ///         if interest.contains(Interest::READABLE) {
+    ///             events |= EPOLLIN;
+    ///         }
+    ///         if interest.contains(Interest::WRITABLE) {
+    ///             events |= EPOLLOUT;
+    ///         }
+    ///         let mut ev = EpollEvent { events, data: fd as u64 };
+    ///         if unsafe { epoll_ctl(self.0, EPOLL_CTL_ADD, fd, &mut ev) } < 0 {
+    ///             return Err(SocketError::last());
+    ///         }
+    ///         Ok(fd as u64)
+    ///     }
+    ///
+    ///     fn deregister(&mut self, fd: RawFd) -> Result<(), SocketError> {
+    ///         let mut ev = EpollEvent { events: 0, data: 0 };
+    ///         if unsafe { epoll_ctl(self.0, EPOLL_CTL_DEL, fd, &mut ev) } < 0 {
+    ///             return Err(SocketError::last());
+    ///         }
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut ep = Epoll(unsafe { epoll_create1(0) });
+    /// let (client, mut server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// let token = server.register_with(&mut ep).unwrap();
+    ///
+    /// let mut buf = [0u8; 16];
+    /// assert_eq!(server.read(&mut buf), Err(SocketError::WouldBlock));
+    /// client.write_all(b"ready").unwrap();
+    /// let mut ev = EpollEvent { events: 0, data: 0 };
+    /// assert_eq!(unsafe { epoll_wait(ep.0, &mut ev, 1, 1000) }, 1);
+    /// assert_eq!({ ev.data }, token);
+    /// assert_eq!(server.read(&mut buf), Ok(5));
+    /// ep.deregister(server.as_raw_fd()).unwrap();
+    /// ```
+    pub fn register_with<R: Reactor>(&mut self, reactor: &mut R) -> Result<R::Token, SocketError> {
+        self.nonblock();
+        reactor.register(self.fd, self.readiness_interest())
+    }
+
+    /// Mark the socket as accepting connections.
+    ///
+    /// The kernel silently clamps `backlog` to the system maximum, so asking
+    /// for more than `Socket::max_backlog()` has no further effect.
+    pub fn listen(&mut self, backlog: i32) -> i32 {
+        unsafe { listen(self.fd, backlog) }
+    }
+
+    /// The largest listen backlog the kernel will honor, read from
+    /// `/proc/sys/net/core/somaxconn`. Falls back to the `SOMAXCONN`
+    /// constant when that can't be read.
+    pub fn max_backlog() -> i32 {
+        std::fs::read_to_string("/proc/sys/net/core/somaxconn")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(consts::SOMAXCONN)
+    }
+
+    /// Create a TCP socket bound to `addr:port` and listening. A `backlog`
+    /// of `None` uses `Socket::max_backlog()`.
+    ///
+    /// `SO_REUSEADDR` is set before binding, so a restarted server can bind
+    /// again while old connections are still in `TIME_WAIT`.
+    pub fn listen_inet(addr: u32, port: u16, backlog: Option<i32>) -> Result<Socket, SocketError> {
+        let mut sock = Self::create(AddressFamily::Inet, SocketType::Stream, IpProto::Ip)?;
+        sock.setsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEADDR, 1)?;
+        sock.bind(BindFamily::Inet(addr, port))?;
+        if sock.listen(backlog.unwrap_or_else(Self::max_backlog)) < 0 {
+            return Err(SocketError::last());
+        }
+        Ok(sock)
+    }
+
+    /// Create a datagram socket bound to `addr` with `SO_REUSEPORT` set,
+    /// meant to be called once per worker thread or process. Every socket
+    /// bound this way to the same address and port (by the same user)
+    /// shares it, and the kernel spreads incoming datagrams across them by
+    /// a hash of the sender's address and port, so each sender sticks to
+    /// one socket. Linux-only: elsewhere `SO_REUSEPORT` doesn't balance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let a = Socket::bind_reuseport(BindFamily::Inet(lo, 0)).unwrap();
+    /// let port = a.local_port().unwrap();
+    /// let b = Socket::bind_reuseport(BindFamily::Inet(lo, port)).unwrap();
+    ///
+    /// // Many senders, each from its own port, so both sockets get some.
+    /// for _ in 0..64 {
+    ///     let tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    ///     tx.send_empty(&BindFamily::Inet(lo, port)).unwrap();
+    /// }
+    /// let count = |s: &Socket| {
+    ///     let mut buf = [0u8; 1];
+    ///     std::iter::from_fn(|| s.recvfrom(&mut buf, MsgFlags::DONTWAIT).ok()).count()
+    /// };
+    /// let (na, nb) = (count(&a), count(&b));
+    /// assert_eq!(na + nb, 64);
+    /// assert!(na > 0 && nb > 0);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn bind_reuseport(addr: BindFamily) -> Result<Socket, SocketError> {
+        let mut sock = Self::create(addr.family(), SocketType::DataGram, IpProto::Ip)?;
+        sock.setsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEPORT, 1)?;
+        sock.bind(addr)?;
+        Ok(sock)
+    }
+
+    /// Create a datagram socket connected to `remote`, so `read` and
+    /// `write` work without naming the peer each time and datagrams from
+    /// anyone else are dropped. It is bound to `local` first if given;
+    /// otherwise the kernel picks the local address and port when
+    /// connecting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, SockAddr, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut server = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// server.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let remote = BindFamily::Inet(lo, server.local_port().unwrap());
+    ///
+    /// let client = Socket::udp_client(None, remote).unwrap();
+    /// client.write(b"request").unwrap();
+    /// let mut buf = [0u8; 16];
+    /// let (len, from) = server.recvfrom(&mut buf, MsgFlags::NONE).unwrap();
+    /// assert_eq!(&buf[..len as usize], b"request");
+    /// match from {
+    ///     SockAddr::Inet(a) => assert_eq!(u16::from_be(a.port), client.local_port().unwrap()),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn udp_client(local: Option<BindFamily>, remote: BindFamily) -> Result<Socket, SocketError> {
+        let mut sock = Self::create(remote.family(), SocketType::DataGram, IpProto::Ip)?;
+        if let Some(local) = local {
+            sock.bind(local)?;
+        }
+        if safe::safe_connect(sock.fd, remote) < 0 {
+            return Err(SocketError::last());
+        }
+        Ok(sock)
+    }
+
+    /// Create a Unix stream socket bound to `path` and listening. A `backlog`
+    /// of `None` uses `Socket::max_backlog()`.
+    ///
+    /// Unix sockets leave their file behind when closed, which would make
+    /// a restarted server fail with `SocketError::AddrInUse`. If `path`
+    /// already exists, is a socket and nothing is listening on it any more,
+    /// it is removed before binding. Anything else at `path` (a regular
+    /// file, a directory, or a socket some other process is still serving)
+    /// is left untouched and reported as `AddrInUse`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError};
+    ///
+    /// let path = std::env::temp_dir().join(format!("mzsocket-doc-{}.sock", std::process::id()));
+    /// let path = path.to_str().unwrap();
+    ///
+    /// let first = Socket::listen_unix(path, None).unwrap();
+    /// // Still being served, so it must not be taken over.
+    /// assert_eq!(Socket::listen_unix(path, None).err(), Some(SocketError::AddrInUse));
+    /// drop(first);
+    /// // Stale now: the leftover file is cleaned up.
+    /// let _second = Socket::listen_unix(path, None).unwrap();
+    /// std::fs::remove_file(path).unwrap();
+    ///
+    /// // Never removes something that isn't a socket.
+    /// std::fs::write(path, b"precious").unwrap();
+    /// assert_eq!(Socket::listen_unix(path, None).err(), Some(SocketError::AddrInUse));
+    /// assert_eq!(std::fs::read(path).unwrap(), b"precious");
+    /// std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn listen_unix(path: &str, backlog: Option<i32>) -> Result<Socket, SocketError> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let is_socket = std::fs::symlink_metadata(path)
+            .map(|m| m.file_type().is_socket())
+            .unwrap_or(false);
+        if is_socket {
+            let probe = Self::create(AddressFamily::Unix, SocketType::Stream, IpProto::Ip)?;
+            if safe::safe_connect(probe.fd, BindFamily::Unix(path.to_string())) < 0
+                && SocketError::last() == SocketError::ConnectionRefused
+            {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        let mut sock = Self::create(AddressFamily::Unix, SocketType::Stream, IpProto::Ip)?;
+        sock.bind(BindFamily::Unix(path.to_string()))?;
+        if sock.listen(backlog.unwrap_or_else(Self::max_backlog)) < 0 {
+            return Err(SocketError::last());
+        }
+        Ok(sock)
+    }
+
+    /// Create a connected `(client, server)` pair for tests, without any
+    /// fixed port that could clash with something else.
+    ///
+    /// For `SocketType::Stream` this is a real TCP connection over
+    /// 127.0.0.1 on a port picked by the kernel. Other types are created
+    /// with `socketpair` as a pair of Unix sockets of that type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{MsgFlags, Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"hello").unwrap();
+    /// let mut buf = [0u8; 5];
+    /// server.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"hello");
+    ///
+    /// let (a, b) = Socket::loopback_pair(SocketType::DataGram).unwrap();
+    /// a.send(b"one datagram", MsgFlags::NONE).unwrap();
+    /// let mut buf = [0u8; 64];
+    /// assert_eq!(b.recv(&mut buf, MsgFlags::NONE).unwrap(), 12);
+    /// ```
+    pub fn loopback_pair(st: SocketType) -> Result<(Socket, Socket), SocketError> {
+        if st != SocketType::Stream {
+            let mut fds = [-1; 2];
+            if safe::safe_socketpair(AddressFamily::Unix, st, &mut fds) < 0 {
+                return Err(SocketError::last());
+            }
+            return Ok((
+                Self::from_fd(fds[0], AddressFamily::Unix),
+                Self::from_fd(fds[1], AddressFamily::Unix),
+            ));
+        }
+        let lo = 0x7f00_0001;
+        let mut listener = Self::listen_inet(lo, 0, Some(1))?;
+        let port = listener.local_port()?;
+        let client = Self::create(AddressFamily::Inet, SocketType::Stream, IpProto::Ip)?;
+        if safe::safe_connect(client.fd, BindFamily::Inet(lo, port)) < 0 {
+            return Err(SocketError::last());
+        }
+        let (server, _) = listener.accept()?;
+        Ok((client, server))
+    }
+
+    /// Create a connected `(client, server)` TCP pair over 127.0.0.1 with
+    /// `TCP_NODELAY` set on both ends, for benchmarks and tests that need
+    /// real TCP rather than a `socketpair`, whose kernel paths differ.
+    /// Nagle is off so small request/response exchanges aren't delayed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::Socket;
+    ///
+    /// let (client, server) = Socket::tcp_loopback_pair().unwrap();
+    /// assert!(client.nodelay().unwrap() && server.nodelay().unwrap());
+    /// client.write_all(b"ping").unwrap();
+    /// let mut buf = [0u8; 4];
+    /// server.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"ping");
+    /// ```
+    pub fn tcp_loopback_pair() -> Result<(Socket, Socket), SocketError> {
+        let (mut client, mut server) = Self::loopback_pair(SocketType::Stream)?;
+        client.set_nodelay(true)?;
+        server.set_nodelay(true)?;
+        Ok((client, server))
+    }
+
+    /// The local IPv4 address and port of this socket (`getsockname`).
+    /// Sockets of other families fail with `EAFNOSUPPORT`.
+    pub fn local_addr(&self) -> Result<InetSockAddr, SocketError> {
+        match self.sockname()? {
+            SockAddr::Inet(a) => Ok(a),
+            _ => Err(SocketError::Other(error::EAFNOSUPPORT)),
+        }
+    }
+
+    /// The local port of an IPv4 or IPv6 socket, in host byte order. After
+    /// binding to port 0 this is the port the kernel picked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let port = listener.local_port().unwrap();
+    /// assert_ne!(port, 0);
+    ///
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, port)).unwrap();
+    /// assert_eq!(u16::from_be(listener.local_addr().unwrap().port), port);
+    /// ```
+    pub fn local_port(&self) -> Result<u16, SocketError> {
+        match self.sockname()? {
+            SockAddr::Inet(a) => Ok(u16::from_be(a.port)),
+            SockAddr::Inet6(a) => Ok(u16::from_be(a.port)),
+            _ => Err(SocketError::Other(error::EAFNOSUPPORT)),
+        }
+    }
+
+    /// The local address this socket is bound to (`getsockname`).
+    fn sockname(&self) -> Result<SockAddr, SocketError> {
+        let mut addr = SockAddr::new(self.af)?;
+        let (ptr, mut slen) = addr.as_mut_raw();
+        if unsafe { getsockname(self.fd, ptr, &mut slen) } < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(addr)
+        }
+    }
+
+    /// Accept a connection, returning the new socket and the peer address
+    /// in the listener's address family.
+    pub fn accept(&mut self) -> Result<(Socket, SockAddr), SocketError> {
+        let mut addr = SockAddr::new(self.af)?;
+        let sock = self.accept_into(&mut addr)?;
+        Ok((sock, addr))
+    }
+
+    /// Accept a connection if one is waiting, or return `Ok(None)` straight
+    /// away if not, whether or not the listener is non-blocking.
+    ///
+    /// The queue is checked with a zero-timeout `poll` first. On a
+    /// blocking listener shared with another thread, that thread can take
+    /// the connection in between, and the accept then waits for the next.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// assert!(listener.try_accept().unwrap().is_none());
+    ///
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, listener.local_port().unwrap())).unwrap();
+    /// assert!(listener.try_accept().unwrap().is_some());
+    /// assert!(listener.try_accept().unwrap().is_none());
+    /// ```
+    pub fn try_accept(&mut self) -> Result<Option<(Socket, SockAddr)>, SocketError> {
+        loop {
+            match safe::safe_poll_one(self.fd, consts::POLLIN, 0) {
+                0 => return Ok(None),
+                r if r < 0 => match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                },
+                _ => {}
+            }
+            return match self.accept() {
+                Ok(conn) => Ok(Some(conn)),
+                Err(SocketError::WouldBlock) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+    }
+
+    /// Accept every connection that is waiting, pushing each onto `out`,
+    /// and return how many there were.
+    ///
+    /// With edge-triggered epoll the listener is only reported ready once
+    /// per burst of connections, so a server has to keep accepting until
+    /// the queue is empty or it will miss some. This does that, stopping
+    /// at `SocketError::WouldBlock`. The listener must be non-blocking, or
+    /// the last call waits for a connection that may never come.
+    /// Connections the peer abandoned before they were accepted are
+    /// skipped. On any other error, connections accepted so far stay in
+    /// `out`.
+    ///
+    /// Running out of file descriptors (`EMFILE`/`ENFILE`) leaves the
+    /// connections queued, so the listener stays ready and a naive loop
+    /// spins. With a guard fd from `reserve_guard_fd`, each such
+    /// connection is accepted and closed straight away instead. Without
+    /// one, this pauses for `set_accept_backoff` and tries once more
+    /// before returning the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// listener.nonblock();
+    /// let port = listener.local_port().unwrap();
+    /// let mut clients = Vec::new();
+    /// for _ in 0..3 {
+    ///     let mut c = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    ///     c.connect(BindFamily::Inet(lo, port)).unwrap();
+    ///     clients.push(c);
+    /// }
+    /// let mut conns = Vec::new();
+    /// assert_eq!(listener.accept_all(&mut conns).unwrap(), 3);
+    /// assert_eq!(listener.accept_all(&mut conns).unwrap(), 0);
+    /// ```
+    pub fn accept_all(&mut self, out: &mut Vec<(Socket, SockAddr)>) -> Result<usize, SocketError> {
+        let mut count = 0;
+        loop {
+            match self.accept() {
+                Ok(conn) => {
+                    out.push(conn);
+                    count += 1;
+                }
+                Err(SocketError::WouldBlock) => return Ok(count),
+                Err(SocketError::Interrupted) | Err(SocketError::Other(error::ECONNABORTED)) => {}
+                Err(e @ SocketError::Other(error::EMFILE | error::ENFILE)) => {
+                    // The fd is allocated before the queue is looked at, so
+                    // this keeps failing even once the queue is empty.
+                    if self.guard_fd.is_some() {
+                        match self.shed_one() {
+                            Ok(()) => continue,
+                            Err(SocketError::WouldBlock) => return Ok(count),
+                            Err(_) => return Err(e),
+                        }
+                    }
+                    std::thread::sleep(self.accept_backoff);
+                    match self.accept() {
+                        Ok(conn) => {
+                            out.push(conn);
+                            count += 1;
+                        }
+                        Err(_) => return Err(e),
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// How long `accept_all` pauses when out of file descriptors before
+    /// trying again, if there's no guard fd. The default is 10ms.
+    pub fn set_accept_backoff(&mut self, pause: Duration) {
+        self.accept_backoff = pause;
+    }
+
+    /// Keep a spare file descriptor open on this listener, for `accept_all`
+    /// to use when the process runs out of them.
+    ///
+    /// At the descriptor limit, `accept` fails without taking the waiting
+    /// connection off the queue, so the listener stays readable and a
+    /// server can neither serve nor refuse the client. The guard fd breaks
+    /// the deadlock: close it, accept the connection into the freed slot,
+    /// close that at once so the client sees the connection drop, then
+    /// reopen the guard. Clients are turned away quickly instead of timing
+    /// out, until descriptors free up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// #[repr(C)]
+    /// struct RLimit {
+    ///     cur: u64,
+    ///     max: u64,
+    /// }
+    /// extern "C" {
+    ///     fn getrlimit(resource: i32, limit: *mut RLimit) -> i32;
+    ///     fn setrlimit(resource: i32, limit: *const RLimit) -> i32;
+    /// }
+    /// const RLIMIT_NOFILE: i32 = 7;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// listener.nonblock();
+    /// listener.reserve_guard_fd().unwrap();
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, listener.local_port().unwrap())).unwrap();
+    ///
+    /// // Use up every descriptor under a lowered limit.
+    /// let mut limit = RLimit { cur: 0, max: 0 };
+    /// unsafe { getrlimit(RLIMIT_NOFILE, &mut limit) };
+    /// let lowered = RLimit { cur: 256, max: limit.max };
+    /// assert_eq!(unsafe { setrlimit(RLIMIT_NOFILE, &lowered) }, 0);
+    /// let mut hog = Vec::new();
+    /// while let Ok(f) = std::fs::File::open("/dev/null") {
+    ///     hog.push(f);
+    /// }
+    ///
+    /// // The connection is shed rather than left in the queue.
+    /// let mut conns = Vec::new();
+    /// assert_eq!(listener.accept_all(&mut conns), Ok(0));
+    /// let mut buf = [0u8; 1];
+    /// assert_eq!(client.read(&mut buf), Ok(0));
+    /// drop(hog);
+    /// unsafe { setrlimit(RLIMIT_NOFILE, &limit) };
+    /// ```
+    pub fn reserve_guard_fd(&mut self) -> Result<(), SocketError> {
+        let guard = std::fs::File::open("/dev/null")
+            .map_err(|e| SocketError::from_errno(e.raw_os_error().unwrap_or(error::EINVAL)))?;
+        self.guard_fd = Some(guard.into());
+        Ok(())
+    }
+
+    /// Free the guard fd, accept one connection into its slot and drop
+    /// it, then take the slot back. Returns the accept error, which is
+    /// `WouldBlock` once the queue is empty.
+    fn shed_one(&mut self) -> Result<(), SocketError> {
+        self.guard_fd = None;
+        let mut addr = SockAddr::default();
+        let (ptr, mut slen) = addr.as_mut_raw();
+        let fd = unsafe { accept(self.fd, ptr, &mut slen) };
+        let res = if fd < 0 {
+            Err(SocketError::last())
+        } else {
+            safe::safe_close(fd);
+            Ok(())
+        };
+        let _ = self.reserve_guard_fd();
+        res
+    }
+
+    /// Accept a connection and apply `cfg` to it before handing it back.
+    ///
+    /// Accepted sockets don't reliably inherit options like `TCP_NODELAY`
+    /// or timeouts from the listener, so servers otherwise repeat the same
+    /// setup after every `accept`. If applying an option fails, the new
+    /// connection is closed and the error returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AcceptConfig, AddressFamily, BindFamily, Socket, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, listener.local_port().unwrap())).unwrap();
+    ///
+    /// let cfg = AcceptConfig {
+    ///     nodelay: true,
+    ///     read_timeout: Some(Duration::from_secs(30)),
+    ///     keepalive: Some(Duration::from_secs(60)),
+    ///     ..AcceptConfig::default()
+    /// };
+    /// let (conn, _peer) = listener.accept_configured(&cfg).unwrap();
+    /// assert!(conn.nodelay().unwrap());
+    /// ```
+    pub fn accept_configured(&mut self, cfg: &AcceptConfig) -> Result<(Socket, SockAddr), SocketError> {
+        let (mut sock, addr) = self.accept()?;
+        if cfg.nodelay {
+            sock.set_nodelay(true)?;
+        }
+        if cfg.read_timeout.is_some() {
+            sock.set_read_timeout(cfg.read_timeout)?;
+        }
+        if cfg.write_timeout.is_some() {
+            sock.set_write_timeout(cfg.write_timeout)?;
+        }
+        if cfg.keepalive.is_some() {
+            sock.set_keepalive(cfg.keepalive)?;
+        }
+        Ok((sock, addr))
+    }
+
+    /// Accept a connection with `flags` already set on the new socket.
+    ///
+    /// On Linux this is `accept4`, so a connection is never visible to
+    /// another thread's `fork`/`exec` without `FD_CLOEXEC`, or briefly
+    /// blocking when it should be non-blocking. Elsewhere the flags are set
+    /// with `fcntl` right after `accept`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AcceptFlags, AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    /// use std::os::fd::AsRawFd;
+    ///
+    /// extern "C" {
+    ///     fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+    /// }
+    /// const F_GETFD: i32 = 1;
+    /// const FD_CLOEXEC: i32 = 1;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, listener.local_port().unwrap())).unwrap();
+    ///
+    /// let (conn, _peer) = listener.accept_with(AcceptFlags::CLOEXEC | AcceptFlags::NONBLOCK).unwrap();
+    /// let flags = unsafe { fcntl(conn.as_raw_fd(), F_GETFD) };
+    /// assert_eq!(flags & FD_CLOEXEC, FD_CLOEXEC);
+    /// let mut buf = [0u8; 8];
+    /// assert_eq!(conn.read(&mut buf), Err(SocketError::WouldBlock));
+    /// ```
+    pub fn accept_with(&mut self, flags: AcceptFlags) -> Result<(Socket, SockAddr), SocketError> {
+        let mut addr = SockAddr::new(self.af)?;
+        let (ptr, mut slen) = addr.as_mut_raw();
+        let fd = self.restarting(|| {
+            #[cfg(target_os = "linux")]
+            let fd = unsafe { accept4(self.fd, ptr, &mut slen, flags.0) };
+            #[cfg(not(target_os = "linux"))]
+            let fd = unsafe { accept(self.fd, ptr, &mut slen) };
+            fd as i64
+        })? as c_int;
+        #[allow(unused_mut)]
+        let mut sock = Self::from_fd(fd, self.af);
+        if !addr.is_valid_fill(slen) {
+            return Err(SocketError::Other(error::EINVAL));
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            if flags.contains(AcceptFlags::CLOEXEC) {
+                let fdflags = unsafe { fcntl(fd, consts::F_GETFD, 0) };
+                if fdflags < 0 || unsafe { fcntl(fd, consts::F_SETFD, fdflags | consts::FD_CLOEXEC) } < 0 {
+                    return Err(SocketError::last());
+                }
+            }
+            if flags.contains(AcceptFlags::NONBLOCK) {
+                sock.nonblock();
+            }
+        }
+        Ok((sock, addr))
+    }
+
+    /// Accept a connection, writing the peer address into `addr` instead of
+    /// building a new one.
+    ///
+    /// An accept loop can keep reusing one `SockAddr`. If `addr` holds a
+    /// different family than the listener it is reset first. That only
+    /// happens on the first call.
+    ///
+    /// A peer address that comes back truncated, too short for the family
+    /// or with a different family is rejected with `SocketError::Other`
+    /// (`EINVAL`) and the connection closed, rather than handing out a
+    /// half-filled `SockAddr`. `accept` and `accept_with` check the same.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, SockAddr, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut server = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let port = server.local_port().unwrap();
+    /// let mut addr = SockAddr::default();
+    /// let mut conns = Vec::new();
+    /// let mut clients = Vec::new();
+    /// for _ in 0..3 {
+    ///     let mut c = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    ///     c.connect(BindFamily::Inet(lo, port)).unwrap();
+    ///     clients.push(c);
+    ///     conns.push(server.accept_into(&mut addr).unwrap());
+    ///     match &addr {
+    ///         SockAddr::Inet(peer) => assert_eq!(unsafe { peer.addr.addr8 }, [127, 0, 0, 1]),
+    ///         _ => unreachable!(),
+    ///     }
+    /// }
+    /// ```
+    pub fn accept_into(&mut self, addr: &mut SockAddr) -> Result<Socket, SocketError> {
+        if addr.family() != self.af {
+            *addr = SockAddr::new(self.af)?;
+        }
+        if let SockAddr::Unix(u) = addr {
+            // Unnamed peers only fill in the family, so don't leave the
+            // previous peer's path behind.
+            u.path = [0u8; structs::UNIX_PATH_LEN];
+        }
+        let (ptr, mut slen) = addr.as_mut_raw();
+        let fd = self.restarting(|| unsafe { accept(self.fd, ptr, &mut slen) } as i64)?;
+        let sock = Self::from_fd(fd as c_int, self.af);
+        if !addr.is_valid_fill(slen) {
+            // Dropping `sock` closes the connection.
+            return Err(SocketError::Other(error::EINVAL));
+        }
+        Ok(sock)
+    }
+
+    /// `accept` on an `Inet` listener, returning the peer as an
+    /// `InetSockAddr`. Other listeners fail with `EAFNOSUPPORT`.
+    pub fn acceptinet(&mut self) -> Result<(Socket, InetSockAddr), SocketError> {
+        match self.accept_family(AddressFamily::Inet)? {
+            (sock, SockAddr::Inet(addr)) => Ok((sock, addr)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// `accept` on an `Inet6` listener; see `acceptinet`.
+    pub fn acceptinet6(&mut self) -> Result<(Socket, Inet6SockAddr), SocketError> {
+        match self.accept_family(AddressFamily::Inet6)? {
+            (sock, SockAddr::Inet6(addr)) => Ok((sock, addr)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// `accept` on a `Unix` listener; see `acceptinet`. An unnamed peer
+    /// comes back with an empty path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    ///
+    /// let path = std::env::temp_dir().join(format!("mzsocket-acceptunix-{}", std::process::id()));
+    /// let path = path.to_str().unwrap();
+    /// let mut server = Socket::listen_unix(path, None).unwrap();
+    /// let mut client = Socket::new(AddressFamily::Unix, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Unix(path.to_string())).unwrap();
+    ///
+    /// let (conn, peer) = server.acceptunix().unwrap();
+    /// assert_eq!(peer.path[0], 0);
+    /// client.write(b"hi").unwrap();
+    /// let mut buf = [0u8; 2];
+    /// assert_eq!(conn.read(&mut buf).unwrap(), 2);
+    ///
+    /// server.nonblock();
+    /// assert_eq!(server.acceptunix().err(), Some(SocketError::WouldBlock));
+    /// std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn acceptunix(&mut self) -> Result<(Socket, UnixSockAddr), SocketError> {
+        match self.accept_family(AddressFamily::Unix)? {
+            (sock, SockAddr::Unix(addr)) => Ok((sock, addr)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn accept_family(&mut self, af: AddressFamily) -> Result<(Socket, SockAddr), SocketError> {
+        if self.af != af {
+            return Err(SocketError::Other(error::EAFNOSUPPORT));
+        }
+        self.accept()
+    }
+
+    /// Connect to `bf`, waiting for the handshake on a blocking socket.
+    ///
+    /// A connect interrupted by a signal carries on in the background, so
+    /// with `restart_on_interrupt` on (the default) this waits for it to
+    /// finish rather than calling `connect` again, and returns how it
+    /// went, e.g. `SocketError::ConnectionRefused`. With it off the result
+    /// is `SocketError::Interrupted` and the connect is left to finish in
+    /// the background; `check_connect` reports on it later.
+    ///
+    /// A stream socket connects once: connecting it again, or connecting
+    /// one returned by `accept`, fails with `SocketError::AlreadyConnected`.
+    /// A datagram socket may be connected any number of times; each call
+    /// just changes its default peer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let server = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let port = server.local_port().unwrap();
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// sock.connect(BindFamily::Inet(lo, port)).unwrap();
+    /// assert_eq!(sock.connect(BindFamily::Inet(lo, port)), Err(SocketError::AlreadyConnected));
+    ///
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let mut udp = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// udp.connect(BindFamily::Inet(lo, 9)).unwrap();
+    /// udp.connect(BindFamily::Inet(lo, rx.local_port().unwrap())).unwrap();
+    /// udp.write(b"moved").unwrap();
+    /// let mut buf = [0u8; 8];
+    /// assert_eq!(rx.read(&mut buf).unwrap(), 5);
+    /// ```
+    pub fn connect(&mut self, bf: BindFamily) -> Result<(), SocketError> {
+        if safe::safe_connect(self.fd, bf) < 0 {
+            match SocketError::last() {
+                SocketError::Interrupted if self.restart_on_interrupt => self.finish_interrupted_connect(),
+                e => Err(e),
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn finish_interrupted_connect(&self) -> Result<(), SocketError> {
+        loop {
+            if safe::safe_poll_one(self.fd, consts::POLLOUT, -1) < 0 {
+                match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                }
+            }
+            if self.check_connect()? == ConnectState::Connected {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Connect to `remote` from the local address `local`, for hosts with
+    /// several addresses where the source matters.
+    ///
+    /// `SO_REUSEADDR` is set before binding, so a fixed `local` port can be
+    /// reused while an earlier connection from it is in `TIME_WAIT`. Use
+    /// port 0 to have the kernel pick one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, SockAddr, Socket, SocketType};
+    ///
+    /// let mut listener = Socket::listen_inet(inet_addr("127.0.0.1").unwrap(), 0, None).unwrap();
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client
+    ///     .connect_from(
+    ///         BindFamily::Inet(inet_addr("127.0.0.2").unwrap(), 0),
+    ///         BindFamily::Inet(inet_addr("127.0.0.1").unwrap(), listener.local_port().unwrap()),
+    ///     )
+    ///     .unwrap();
+    /// let (_conn, peer) = listener.accept().unwrap();
+    /// match peer {
+    ///     SockAddr::Inet(a) => assert_eq!(unsafe { a.addr.addr8 }, [127, 0, 0, 2]),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn connect_from(&mut self, local: BindFamily, remote: BindFamily) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEADDR, 1)?;
+        self.bind(local)?;
+        if safe::safe_connect(self.fd, remote) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Connect to `remote` from a local port in `port_range`, for
+    /// firewalls that only pass certain source ports.
+    ///
+    /// Each port is tried in turn, with `SO_REUSEADDR` set, until a bind
+    /// succeeds; the local address is the wildcard of `remote`'s family.
+    /// `SocketError::AddrInUse` means every port in the range was taken. A
+    /// socket can only be bound once, so if the connect itself fails
+    /// (including `EADDRNOTAVAIL` when the same local port is already
+    /// connected to the same `remote`), use a new socket to try again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, SockAddr, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let remote = BindFamily::Inet(lo, listener.local_port().unwrap());
+    /// // Start the range at a port the kernel just handed out and released.
+    /// let start = {
+    ///     let mut probe = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    ///     probe.bind(BindFamily::Inet(lo, 0)).unwrap();
+    ///     probe.local_port().unwrap()
+    /// };
+    /// let range = start..start.saturating_add(8);
+    /// // The client is closed first, so the TIME_WAIT stays on its side.
+    /// let (_conn, peer) = {
+    ///     let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    ///     client.connect_from_range(remote, range.clone()).unwrap();
+    ///     listener.accept().unwrap()
+    /// };
+    /// match peer {
+    ///     SockAddr::Inet(a) => assert!(range.contains(&u16::from_be(a.port))),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn connect_from_range(&mut self, remote: BindFamily, port_range: std::ops::Range<u16>) -> Result<(), SocketError> {
+        let wildcard = |port| match remote {
+            BindFamily::Inet(..) => Ok(BindFamily::Inet(0, port)),
+            BindFamily::Inet6(..) | BindFamily::Inet6Scoped(..) | BindFamily::Inet6Flow(..) => {
+                Ok(BindFamily::Inet6(0, port))
+            }
+            BindFamily::Unix(_) => Err(SocketError::Other(error::EAFNOSUPPORT)),
+        };
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEADDR, 1)?;
+        let mut bound = false;
+        for port in port_range {
+            match self.bind(wildcard(port)?) {
+                Ok(()) => {
+                    bound = true;
+                    break;
+                }
+                Err(SocketError::AddrInUse) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !bound {
+            return Err(SocketError::AddrInUse);
+        }
+        if safe::safe_connect(self.fd, remote) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Start connecting without waiting for the handshake to finish.
+    ///
+    /// The socket is switched to non-blocking mode and left that way. A
+    /// connection that can't complete immediately returns
+    /// `ConnectState::InProgress`. Wait for the socket to become writable,
+    /// then call `check_connect` to find out how it went.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, ConnectState, Socket, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let server = Socket::listen_inet(lo, 0, None).unwrap();
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// let mut state = sock.connect_nonblocking(BindFamily::Inet(lo, server.local_port().unwrap())).unwrap();
+    /// while state == ConnectState::InProgress {
+    ///     // An event loop would wait for the socket to become writable here
+    ///     // and get on with other work in the meantime.
+    ///     std::thread::sleep(Duration::from_millis(1));
+    ///     state = sock.check_connect().unwrap();
+    /// }
+    /// assert_eq!(state, ConnectState::Connected);
+    /// ```
+    pub fn connect_nonblocking(&mut self, bf: BindFamily) -> Result<ConnectState, SocketError> {
+        self.nonblock();
+        if safe::safe_connect(self.fd, bf) == 0 {
+            return Ok(ConnectState::Connected);
+        }
+        match SocketError::last() {
+            // Only stream sockets get here; datagram connects never block.
+            SocketError::Other(error::EINPROGRESS) => {
+                self.connect_pending.store(true, std::sync::atomic::Ordering::Relaxed);
+                Ok(ConnectState::InProgress)
+            }
+            e => Err(e),
+        }
+    }
+
+    /// Connect to `remote`, waiting for the handshake like `connect` but
+    /// giving up with `SocketError::Cancelled` as soon as `cancel` becomes
+    /// readable, e.g. when a shutdown pipe is written to or closed.
+    ///
+    /// If `cancel` is already readable when the connect is still pending,
+    /// cancellation wins. The socket's blocking mode is restored before
+    /// returning. After a cancellation the connect attempt is left
+    /// unfinished, so the socket should be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, Socket, SocketError, SocketType};
+    /// use std::os::fd::AsRawFd;
+    /// use std::time::Duration;
+    ///
+    /// // A listener that never accepts, with room for one queued
+    /// // connection, leaves the next connect hanging.
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let listener = Socket::listen_inet(lo, 0, Some(0)).unwrap();
+    /// let remote = BindFamily::Inet(lo, listener.local_port().unwrap());
+    /// let mut first = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// first.connect(remote.clone()).unwrap();
+    ///
+    /// let (trigger, cancel) = Socket::loopback_pair(SocketType::DataGram).unwrap();
+    /// let shutdown = std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_millis(50));
+    ///     trigger.send(b"stop", MsgFlags::NONE).unwrap();
+    /// });
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// assert_eq!(
+    ///     sock.connect_cancellable(remote, cancel.as_raw_fd()),
+    ///     Err(SocketError::Cancelled)
+    /// );
+    /// shutdown.join().unwrap();
+    /// ```
+    pub fn connect_cancellable(&mut self, remote: BindFamily, cancel: RawFd) -> Result<(), SocketError> {
+        let was_nonblocking = self.is_nonblocking();
+        let res = self.wait_connect_cancellable(remote, cancel);
+        if !was_nonblocking {
+            self.block();
+        }
+        res
+    }
+
+    fn wait_connect_cancellable(&mut self, remote: BindFamily, cancel: RawFd) -> Result<(), SocketError> {
+        let mut state = self.connect_nonblocking(remote)?;
+        // `cancel` is only borrowed for the poll; the caller still owns it.
+        let cancel = unsafe { std::os::fd::BorrowedFd::borrow_raw(cancel) };
+        while state == ConnectState::InProgress {
+            let mut fds = [
+                PollFd::new(&cancel, PollEvents::IN),
+                PollFd::new(self, PollEvents::OUT),
+            ];
+            match poll(&mut fds, None) {
+                Ok(_) => {}
+                Err(SocketError::Interrupted) => continue,
+                Err(e) => return Err(e),
+            }
+            if fds[0].revents() != PollEvents::NONE {
+                return Err(SocketError::Cancelled);
+            }
+            if fds[1].revents() != PollEvents::NONE {
+                state = self.check_connect()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect to `remote`, giving up with `SocketError::TimedOut` if the
+    /// handshake hasn't finished within `timeout`.
+    ///
+    /// The bound comes from a non-blocking connect and `poll`, with the
+    /// outcome read from `SO_ERROR`; `SO_SNDTIMEO` is not involved, as
+    /// kernels disagree on whether it applies to `connect`. The socket's
+    /// blocking mode is restored before returning. After a timeout the
+    /// connect attempt is left unfinished, so the socket should be
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// // A listener whose queue is full stops answering SYNs.
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let listener = Socket::listen_inet(lo, 0, Some(0)).unwrap();
+    /// let remote = BindFamily::Inet(lo, listener.local_port().unwrap());
+    /// let mut first = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// first.connect_timeout(remote.clone(), Duration::from_secs(1)).unwrap();
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// let start = Instant::now();
+    /// assert_eq!(
+    ///     sock.connect_timeout(remote, Duration::from_millis(200)),
+    ///     Err(SocketError::TimedOut)
+    /// );
+    /// let took = start.elapsed();
+    /// assert!(took >= Duration::from_millis(200) && took < Duration::from_secs(2));
+    /// ```
+    pub fn connect_timeout(&mut self, remote: BindFamily, timeout: Duration) -> Result<(), SocketError> {
+        let was_nonblocking = self.is_nonblocking();
+        let res = self.wait_connect_timeout(remote, Instant::now() + timeout);
+        if !was_nonblocking {
+            self.block();
+        }
+        res
+    }
+
+    fn wait_connect_timeout(&mut self, remote: BindFamily, deadline: Instant) -> Result<(), SocketError> {
+        if self.connect_nonblocking(remote)? == ConnectState::InProgress {
+            self.await_connected(deadline)?;
+        }
+        Ok(())
+    }
+
+    /// Wait until a connect started with `connect_nonblocking` finishes,
+    /// giving up with `SocketError::TimedOut` at `deadline`. A failed
+    /// connect returns its own error, such as
+    /// `SocketError::ConnectionRefused`, read from `SO_ERROR`.
+    ///
+    /// Writability alone isn't trusted: after every wakeup the outcome is
+    /// checked with `check_connect`, and a wakeup that finds the handshake
+    /// still in progress (or a `poll` that returns early) just waits
+    /// again, so spurious wakeups can't be mistaken for success. A socket
+    /// with no connect in progress at all fails with `ENOTCONN` as
+    /// `SocketError::Other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, ConnectState, Socket, SocketError, SocketType};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let listener = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let port = listener.local_port().unwrap();
+    /// let deadline = Instant::now() + Duration::from_secs(5);
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// if sock.connect_nonblocking(BindFamily::Inet(lo, port)).unwrap() == ConnectState::InProgress {
+    ///     sock.await_connected(deadline).unwrap();
+    /// }
+    ///
+    /// drop(listener);
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// let res = match sock.connect_nonblocking(BindFamily::Inet(lo, port)) {
+    ///     Ok(_) => sock.await_connected(deadline),
+    ///     Err(e) => Err(e),
+    /// };
+    /// assert_eq!(res, Err(SocketError::ConnectionRefused));
+    /// ```
+    pub fn await_connected(&self, deadline: Instant) -> Result<(), SocketError> {
+        loop {
+            let mut fds = [PollFd::new(self, PollEvents::OUT)];
+            match poll(&mut fds, Some(deadline.saturating_duration_since(Instant::now()))) {
+                Ok(0) if Instant::now() >= deadline => return Err(SocketError::TimedOut),
+                Ok(0) | Err(SocketError::Interrupted) => continue,
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+            match self.check_connect()? {
+                ConnectState::Connected => return Ok(()),
+                ConnectState::InProgress if fds[0].revents().contains(PollEvents::HUP) => {
+                    return Err(SocketError::Other(error::ENOTCONN))
+                }
+                ConnectState::InProgress => {}
+            }
+        }
+    }
+
+    /// Find out how a connect started with `connect_nonblocking` is doing.
+    ///
+    /// A failed connect returns the error it failed with, as taken by
+    /// `take_error`. Otherwise the result says whether the handshake has
+    /// finished yet.
+    pub fn check_connect(&self) -> Result<ConnectState, SocketError> {
+        let state = match self.take_error()? {
+            Some(e) => Err(e),
+            None => self.peer_state(),
+        };
+        if state != Ok(ConnectState::InProgress) {
+            self.connect_pending.store(false, std::sync::atomic::Ordering::Relaxed);
+        }
+        state
+    }
+
+    /// Whether the socket has a peer yet, going by `getpeername` alone, so
+    /// without consuming a pending error.
+    fn peer_state(&self) -> Result<ConnectState, SocketError> {
+        let mut peer = SockAddr::new(self.af)?;
+        let (ptr, mut slen) = peer.as_mut_raw();
+        if unsafe { getpeername(self.fd, ptr, &mut slen) } == 0 {
+            return Ok(ConnectState::Connected);
+        }
+        match SocketError::last() {
+            SocketError::Other(error::ENOTCONN) => Ok(ConnectState::InProgress),
+            e => Err(e),
+        }
+    }
+
+    /// Take the socket's pending error, if any, clearing it.
+    ///
+    /// This is the asynchronous error the kernel keeps in `SO_ERROR`, such
+    /// as a refused non-blocking connect or an ICMP error on a connected
+    /// datagram socket. An error already fetched by `has_error` is returned
+    /// first.
+    pub fn take_error(&self) -> Result<Option<SocketError>, SocketError> {
+        let e = match self.pending_error.swap(0, std::sync::atomic::Ordering::Relaxed) {
+            0 => self.getsockopt_int(consts::SOL_SOCKET, consts::SO_ERROR)?,
+            e => e,
+        };
+        Ok((e != 0).then(|| SocketError::from_errno(e)))
+    }
+
+    /// Whether the socket has a pending error, without losing it: a cheap
+    /// check for event loops after `poll` reports `PollEvents::ERR`.
+    ///
+    /// The kernel's `SO_ERROR` is one-shot, so reading it at all clears
+    /// it. To keep the error, this stores what it reads on the `Socket`,
+    /// and it stays pending (and `has_error` keeps returning `true`) until
+    /// `take_error` or `check_connect` hands it out. Only the first error
+    /// is kept; the kernel reports any later one in its own right.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, poll, AddressFamily, BindFamily, PollEvents, PollFd, Socket, SocketError, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// // Find a port nobody is listening on.
+    /// let mut probe = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// probe.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let port = probe.local_port().unwrap();
+    /// drop(probe);
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// assert!(!sock.has_error().unwrap());
+    /// let _ = sock.connect_nonblocking(BindFamily::Inet(lo, port));
+    /// let mut fds = [PollFd::new(&sock, PollEvents::OUT)];
+    /// poll(&mut fds, Some(Duration::from_secs(5))).unwrap();
+    ///
+    /// assert!(sock.has_error().unwrap());
+    /// assert!(sock.has_error().unwrap());
+    /// assert_eq!(sock.take_error().unwrap(), Some(SocketError::ConnectionRefused));
+    /// assert!(!sock.has_error().unwrap());
+    /// ```
+    pub fn has_error(&self) -> Result<bool, SocketError> {
+        use std::sync::atomic::Ordering;
+        if self.pending_error.load(Ordering::Relaxed) != 0 {
+            return Ok(true);
+        }
+        match self.getsockopt_int(consts::SOL_SOCKET, consts::SO_ERROR)? {
+            0 => Ok(false),
+            e => {
+                let _ = self.pending_error.compare_exchange(0, e, Ordering::Relaxed, Ordering::Relaxed);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Choose what `read`, `write`, `send`, `sendto`, `recv`, `recvfrom`,
+    /// `connect` and the `accept` calls do when a signal interrupts them
+    /// (`EINTR`): retry transparently, the default, or fail with
+    /// `SocketError::Interrupted` so the caller can react to the signal
+    /// first. `write_all` and `BufWriter` follow the same choice. Sockets
+    /// returned by `accept` start with the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (mut client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// assert!(client.restart_on_interrupt());
+    /// client.set_restart_on_interrupt(false);
+    /// assert!(!client.restart_on_interrupt());
+    /// ```
+    pub fn set_restart_on_interrupt(&mut self, on: bool) {
+        self.restart_on_interrupt = on;
+    }
+
+    /// Whether interrupted calls are retried; see `set_restart_on_interrupt`.
+    pub fn restart_on_interrupt(&self) -> bool {
+        self.restart_on_interrupt
+    }
+
+    /// Run a syscall returning -1 on error, retrying it on `EINTR` if
+    /// `restart_on_interrupt` is set.
+    fn restarting(&self, mut call: impl FnMut() -> i64) -> Result<i64, SocketError> {
+        loop {
+            let ret = call();
+            if ret >= 0 {
+                return Ok(ret);
+            }
+            match SocketError::last() {
+                SocketError::Interrupted if self.restart_on_interrupt => {}
+                e => return Err(e),
+            }
+        }
+    }
+
+    /// Read into `buffer`, returning the number of bytes read. A peer
+    /// that closed cleanly reads as 0; one that reset the connection gives
+    /// `SocketError::ConnectionReset`.
+    ///
+    /// When a read timeout is set, a read interrupted by a signal is retried
+    /// with only the time remaining until the original deadline, measured
+    /// on a monotonic clock, so a stream of signals cannot extend the wait.
+    /// `SocketError::TimedOut` is returned once that deadline has passed.
+    /// `recv`, `recvfrom` and `peek` wait the same way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError, SocketType};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let (_client, mut server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// server.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    /// let start = Instant::now();
+    /// let mut buf = [0u8; 8];
+    /// assert_eq!(server.read(&mut buf), Err(SocketError::TimedOut));
+    /// assert!(start.elapsed() >= Duration::from_millis(100));
+    /// ```
+    pub fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        let ret = self.with_read_deadline(false, || unsafe {
+            read(self.fd, buffer.as_mut_ptr(), buffer.len() as u64)
+        });
+        #[cfg(feature = "stats")]
+        if let Ok(n) = ret {
+            self.bytes_read.fetch_add(n as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+        ret
+    }
+
+    /// Run a receive call returning -1 on error against the read timeout
+    /// as one deadline: on `EINTR` it is retried with `SO_RCVTIMEO` cut to
+    /// the time left, and an expired timeout becomes
+    /// `SocketError::TimedOut`. `dontwait` marks a call that was never
+    /// going to wait (`MSG_DONTWAIT`), whose `WouldBlock` is passed on.
+    fn with_read_deadline(&self, dontwait: bool, mut call: impl FnMut() -> i64) -> Result<i64, SocketError> {
+        let deadline = self.read_timeout.map(|t| Instant::now() + t);
+        let mut rearmed = false;
+        let ret = loop {
+            let ret = call();
+            if ret >= 0 {
+                break Ok(ret);
+            }
+            let err = SocketError::last();
+            let deadline = match (err, deadline) {
+                (SocketError::Interrupted, _) if !self.restart_on_interrupt => break Err(err),
+                (SocketError::Interrupted, Some(d)) => d,
+                (SocketError::WouldBlock, Some(d)) if !dontwait && !self.is_nonblocking() => d,
+                (SocketError::Interrupted, None) => continue,
+                _ => break Err(err),
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                break Err(SocketError::TimedOut);
+            }
+            if let Err(e) = self.set_rcvtimeo(Some(deadline - now)) {
+                break Err(e);
+            }
+            rearmed = true;
+        };
+        if rearmed {
+            self.set_rcvtimeo(self.read_timeout)?;
+        }
+        ret
+    }
+
+    /// Receive into `buffer` with the given flags, returning the number of
+    /// bytes received. The read timeout applies as for `read`.
+    pub fn recv(&self, buffer: &mut [u8], flags: MsgFlags) -> Result<i64, SocketError> {
+        self.with_read_deadline(flags.0 & MsgFlags::DONTWAIT.0 != 0, || unsafe {
+            recv(self.fd, buffer.as_mut_ptr(), buffer.len() as u64, flags.0)
+        })
+    }
+
+    /// Receive one frame on a packet (`AF_PACKET`) socket, along with the
+    /// link-layer address it came from.
+    ///
+    /// On a socket bound to all interfaces, `LinkAddr::ifindex` tells which
+    /// one the frame arrived on, `packet_type()` whether it was addressed to
+    /// this host, broadcast, multicast, another host or sent by us, and
+    /// `hatype` the interface's hardware type.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mzsocket::{AddressFamily, IpProto, PacketType, Socket, SocketType};
+    ///
+    /// // ETH_P_ALL in network byte order. Needs CAP_NET_RAW.
+    /// let proto = IpProto::Unknown(0x0003_u16.to_be() as i32);
+    /// let sock = Socket::new(AddressFamily::Packet, SocketType::DataGram, Some(proto)).unwrap();
+    /// let mut frame = [0u8; 2048];
+    /// let (len, from) = sock.recv_link(&mut frame).unwrap();
+    /// if from.packet_type() == PacketType::Outgoing {
+    ///     println!("sent {} bytes on interface {}", len, from.ifindex);
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_link(&self, buffer: &mut [u8]) -> Result<(i64, LinkAddr), SocketError> {
+        let mut addr = LinkAddr::default();
+        let mut slen = size_of::<LinkAddr>() as u32;
+        let len = self.recvfrom_raw(
+            buffer,
+            MsgFlags::NONE,
+            &mut addr as *mut LinkAddr as *mut c_void,
+            &mut slen,
+        )?;
+        Ok((len, addr))
+    }
+
+    /// Take the oldest error off the socket's error queue, or `None` when
+    /// the queue is empty. Never blocks.
+    ///
+    /// Linux only queues errors (ICMP unreachables, path MTU changes and
+    /// the like) once `IP_RECVERR`/`IPV6_RECVERR` is on. This call turns
+    /// it on, so call it once right after creating the socket; errors from
+    /// before that are not queued.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, ErrOrigin, MsgFlags, Socket, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// assert!(sock.recv_error().unwrap().is_none());
+    ///
+    /// // Borrow a free port and release it, so nothing is listening there
+    /// // and an ICMP port unreachable comes back.
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let port = {
+    ///     let mut probe = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    ///     probe.bind(BindFamily::Inet(lo, 0)).unwrap();
+    ///     probe.local_port().unwrap()
+    /// };
+    /// sock.connect(BindFamily::Inet(lo, port)).unwrap();
+    /// sock.send(b"anyone?", MsgFlags::NONE).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_millis(100));
+    /// let err = sock.recv_error().unwrap().unwrap();
+    /// assert_eq!(err.origin, ErrOrigin::Icmp);
+    /// assert_eq!((err.kind, err.code), (3, 3));
+    /// assert!(sock.recv_error().unwrap().is_none());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_error(&self) -> Result<Option<SockExtendedErr>, SocketError> {
+        let (level, name) = match self.af {
+            AddressFamily::Inet6 => (consts::SOL_IPV6, consts::IPV6_RECVERR),
+            _ => (consts::SOL_IP, consts::IP_RECVERR),
+        };
+        self.setsockopt_int(level, name, 1)?;
+        let mut data = [0u8; 0];
+        let mut control = [0u8; 256];
+        let (r, clen) = safe::safe_recvmsg(self.fd, &mut data, &mut control, MsgFlags::ERRQUEUE.0);
+        if r < 0 {
+            return match SocketError::last() {
+                SocketError::WouldBlock => Ok(None),
+                e => Err(e),
+            };
+        }
+        Ok(safe::find_cmsg(&control[..clen], level, name).and_then(SockExtendedErr::from_cmsg))
+    }
+
+    /// Send `data` along with open file descriptors (`SCM_RIGHTS`) over a
+    /// Unix domain socket. The peer gets duplicates of `fds`, so they can
+    /// be closed here once this returns.
+    ///
+    /// On a stream socket `data` must not be empty, since the descriptors
+    /// travel with the first byte.
+    pub fn send_fds(&self, data: &[u8], fds: &[RawFd]) -> Result<i64, SocketError> {
+        let raw: Vec<u8> = fds.iter().flat_map(|fd| fd.to_ne_bytes()).collect();
+        let control = safe::build_cmsg(consts::SOL_SOCKET, consts::SCM_RIGHTS, &raw);
+        let r = safe::safe_sendmsg(self.fd, data, &control, 0);
+        if r < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(r)
+        }
+    }
+
+    /// Receive into `buffer` along with up to `max_fds` file descriptors
+    /// sent with `send_fds`.
+    ///
+    /// The descriptors are created close-on-exec (`MSG_CMSG_CLOEXEC`), so
+    /// none leak into a child if another thread forks and execs before
+    /// they can be marked. The kernel closes any beyond `max_fds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    /// use std::os::fd::AsRawFd;
+    ///
+    /// extern "C" {
+    ///     fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+    /// }
+    /// const F_GETFD: i32 = 1;
+    /// const FD_CLOEXEC: i32 = 1;
+    ///
+    /// let (a, b) = Socket::loopback_pair(SocketType::DataGram).unwrap();
+    /// let file = std::fs::File::open("/dev/null").unwrap();
+    /// a.send_fds(b"fd", &[file.as_raw_fd()]).unwrap();
+    ///
+    /// let mut buf = [0u8; 16];
+    /// let (len, fds) = b.recv_fds(&mut buf, 4).unwrap();
+    /// assert_eq!(&buf[..len as usize], b"fd");
+    /// assert_eq!(fds.len(), 1);
+    /// assert_ne!(fds[0].as_raw_fd(), file.as_raw_fd());
+    /// let flags = unsafe { fcntl(fds[0].as_raw_fd(), F_GETFD) };
+    /// assert_eq!(flags & FD_CLOEXEC, FD_CLOEXEC);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_fds(&self, buffer: &mut [u8], max_fds: usize) -> Result<(i64, Vec<std::os::fd::OwnedFd>), SocketError> {
+        let fd_size = size_of::<RawFd>();
+        let mut control = vec![0u8; safe::cmsg_space(max_fds * fd_size)];
+        let (r, clen) = safe::safe_recvmsg(self.fd, buffer, &mut control, consts::MSG_CMSG_CLOEXEC);
+        if r < 0 {
+            return Err(SocketError::last());
+        }
+        let fds = safe::find_cmsg(&control[..clen], consts::SOL_SOCKET, consts::SCM_RIGHTS)
+            .unwrap_or(&[])
+            .chunks_exact(fd_size)
+            .map(|c| unsafe { std::os::fd::OwnedFd::from_raw_fd(RawFd::from_ne_bytes([c[0], c[1], c[2], c[3]])) })
+            .collect();
+        Ok((r, fds))
+    }
+
+    /// Have the kernel attach the sender's credentials to every message
+    /// arriving on this Unix socket (`SO_PASSCRED`), for `recv_with_cred`.
+    ///
+    /// Credentials are attached when a message is sent, so turn this on
+    /// before the peer sends: anything already queued arrives without
+    /// them.
+    #[cfg(target_os = "linux")]
+    pub fn set_passcred(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_PASSCRED, on as c_int)
+    }
+
+    /// Receive into `buffer` on a Unix socket along with the sender's
+    /// credentials, passed by the kernel with the data (`SCM_CREDENTIALS`).
+    /// `set_passcred` must be on; without it this fails with `EINVAL`.
+    ///
+    /// The kernel fills these in itself, so unlike anything in the data
+    /// they can't be forged, short of the privilege to claim other ids.
+    /// On a datagram socket each message is checked on its own, which
+    /// matters when several processes send to one server socket. A message
+    /// sent before `set_passcred` took effect reports pid 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{MsgFlags, Socket, SocketType};
+    ///
+    /// extern "C" {
+    ///     fn getuid() -> u32;
+    /// }
+    ///
+    /// let (a, mut b) = Socket::loopback_pair(SocketType::DataGram).unwrap();
+    /// b.set_passcred(true).unwrap();
+    /// a.send(b"who am i", MsgFlags::NONE).unwrap();
+    /// let mut buf = [0u8; 16];
+    /// let (len, cred) = b.recv_with_cred(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len as usize], b"who am i");
+    /// assert_eq!(cred.pid as u32, std::process::id());
+    /// assert_eq!(cred.uid, unsafe { getuid() });
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_with_cred(&self, buffer: &mut [u8]) -> Result<(i64, PeerCred), SocketError> {
+        let mut control = vec![0u8; safe::cmsg_space(size_of::<PeerCred>())];
+        let (r, clen) = safe::safe_recvmsg(self.fd, buffer, &mut control, 0);
+        if r < 0 {
+            return Err(SocketError::last());
+        }
+        match safe::find_cmsg(&control[..clen], consts::SOL_SOCKET, consts::SCM_CREDENTIALS) {
+            Some(data) if data.len() >= size_of::<PeerCred>() => {
+                Ok((r, unsafe { std::ptr::read_unaligned(data.as_ptr() as *const PeerCred) }))
+            }
+            _ => Err(SocketError::Other(error::EINVAL)),
+        }
+    }
+
+    /// Receive a datagram on an IPv4 socket along with its sender and the
+    /// TTL it arrived with (`IP_RECVTTL`, turned on by the first call).
+    ///
+    /// A peer that sends with TTL 255 can be checked to be on the local
+    /// link by requiring 255 here, since any router on the way would have
+    /// decremented it (GTSM, RFC 5082).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// tx.sendto(b"hello", &BindFamily::Inet(lo, rx.local_port().unwrap())).unwrap();
+    ///
+    /// let mut buf = [0u8; 16];
+    /// let (len, from, ttl) = rx.recv_with_ttl(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len as usize], b"hello");
+    /// assert_eq!(from.to_string(), format!("127.0.0.1:{}", tx.local_port().unwrap()));
+    /// assert_eq!(Some(ttl as u32), tx.options_snapshot().unwrap().ttl);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_with_ttl(&self, buffer: &mut [u8]) -> Result<(i64, InetSockAddr, u8), SocketError> {
+        self.setsockopt_int(consts::SOL_IP, consts::IP_RECVTTL, 1)?;
+        let mut from = InetSockAddr::default();
+        let (len, ttl) = self.recv_hops(buffer, &mut from, consts::SOL_IP, consts::IP_TTL)?;
+        Ok((len, from, ttl))
+    }
+
+    /// The IPv6 counterpart of `recv_with_ttl`, returning the hop limit
+    /// the datagram arrived with (`IPV6_RECVHOPLIMIT`).
+    #[cfg(target_os = "linux")]
+    pub fn recv_with_hop_limit(&self, buffer: &mut [u8]) -> Result<(i64, Inet6SockAddr, u8), SocketError> {
+        self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_RECVHOPLIMIT, 1)?;
+        let mut from = Inet6SockAddr::default();
+        let (len, hops) = self.recv_hops(buffer, &mut from, consts::SOL_IPV6, consts::IPV6_HOPLIMIT)?;
+        Ok((len, from, hops))
+    }
+
+    /// Receive a datagram on an IPv6 socket along with its sender, the
+    /// local address it was sent to and the index of the interface it
+    /// arrived on (`IPV6_RECVPKTINFO`, turned on by the first call).
+    ///
+    /// A server bound to `::` on a host with several addresses needs this
+    /// to reply from the address the client actually used. The local
+    /// address comes with the socket's port, and with the interface as
+    /// its scope id if it is link-local, so it can be bound to as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let mut server = Socket::new(AddressFamily::Inet6, SocketType::DataGram, None).unwrap();
+    /// server.bind(BindFamily::Inet6(0, 0)).unwrap();
+    /// let port = server.local_port().unwrap();
+    /// let client = Socket::udp_client(None, BindFamily::Inet6(1, port)).unwrap();
+    /// client.write(b"who am i talking to").unwrap();
+    ///
+    /// let mut buf = [0u8; 32];
+    /// let (len, from, to, ifindex) = server.recv_pktinfo6(&mut buf).unwrap();
+    /// assert_eq!(len, 19);
+    /// assert_eq!(u16::from_be(from.port), client.local_port().unwrap());
+    /// assert_eq!(to.to_string(), format!("[::1]:{}", port));
+    /// assert_eq!(ifindex, mzsocket::if_nametoindex("lo").unwrap());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_pktinfo6(&self, buffer: &mut [u8]) -> Result<(i64, Inet6SockAddr, Inet6SockAddr, u32), SocketError> {
+        self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_RECVPKTINFO, 1)?;
+        let mut from = Inet6SockAddr::default();
+        let mut control = [0u8; 64];
+        let (len, clen) = self.recvmsg_from(buffer, &mut from, &mut control)?;
+        // struct in6_pktinfo: the address, then the interface index.
+        let info = match safe::find_cmsg(&control[..clen], consts::SOL_IPV6, consts::IPV6_PKTINFO) {
+            Some(info) if info.len() >= 20 => info,
+            _ => return Err(SocketError::Other(error::EINVAL)),
+        };
+        let ifindex = u32::from_ne_bytes([info[16], info[17], info[18], info[19]]);
+        let mut addr = [0u8; 16];
+        addr.copy_from_slice(&info[..16]);
+        let link_local = addr[0] == 0xfe && addr[1] & 0xc0 == 0x80;
+        let to = Inet6SockAddr {
+            port: self.local_port()?.to_be(),
+            addr: Inet6Addr::new_8(addr),
+            scopeid: if link_local { ifindex } else { 0 },
+            ..Inet6SockAddr::default()
+        };
+        Ok((len, from, to, ifindex))
+    }
+
+    /// `recvmsg` into `buffer` and `from`, picking the int control message
+    /// `level`/`kind` out as a TTL or hop limit.
+    #[cfg(target_os = "linux")]
+    fn recv_hops<A>(&self, buffer: &mut [u8], from: &mut A, level: c_int, kind: c_int) -> Result<(i64, u8), SocketError> {
+        let mut control = [0u8; 64];
+        let (r, clen) = self.recvmsg_from(buffer, from, &mut control)?;
+        match safe::find_cmsg(&control[..clen], level, kind) {
+            Some(&[a, b, c, d, ..]) => Ok((r, c_int::from_ne_bytes([a, b, c, d]) as u8)),
+            _ => Err(SocketError::Other(error::EINVAL)),
+        }
+    }
+
+    /// `recvmsg` into `buffer`, with the sender stored in `from` (a
+    /// sockaddr struct) and ancillary data in `control`. Returns the byte
+    /// count and how much of `control` was filled.
+    #[cfg(target_os = "linux")]
+    fn recvmsg_from<A>(&self, buffer: &mut [u8], from: &mut A, control: &mut [u8]) -> Result<(i64, usize), SocketError> {
+        let (r, clen) = safe::safe_recvmsg_from(
+            self.fd,
+            buffer,
+            control,
+            0,
+            from as *mut A as *mut c_void,
+            size_of::<A>() as c_uint,
+        );
+        if r < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok((r, clen))
+        }
+    }
+
+    /// Receive into `buffer`, also returning the address the data came
+    /// from. Mostly useful on datagram sockets.
+    pub fn recvfrom(&self, buffer: &mut [u8], flags: MsgFlags) -> Result<(i64, SockAddr), SocketError> {
+        let mut addr = SockAddr::new(self.af)?;
+        let (ptr, mut slen) = addr.as_mut_raw();
+        let len = self.recvfrom_raw(buffer, flags, ptr, &mut slen)?;
+        Ok((len, addr))
+    }
+
+    /// Receive one whole IPv4 datagram, however large, into a buffer of
+    /// exactly its size.
+    ///
+    /// The datagram's length is peeked first (`MSG_PEEK | MSG_TRUNC` with
+    /// an empty buffer reports the full length), then it is read for real.
+    /// That is two calls, so this assumes a single reader: another thread
+    /// receiving on the same socket in between could swap in a different
+    /// datagram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let mut tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// tx.connect(BindFamily::Inet(lo, rx.local_port().unwrap())).unwrap();
+    ///
+    /// let big = vec![7u8; 30000];
+    /// tx.send(&big, MsgFlags::NONE).unwrap();
+    /// let (data, from) = rx.recv_exact_datagram().unwrap();
+    /// assert_eq!(data, big);
+    /// assert_eq!(unsafe { from.addr.addr8 }, [127, 0, 0, 1]);
+    /// ```
+    pub fn recv_exact_datagram(&self) -> Result<(Vec<u8>, InetSockAddr), SocketError> {
+        if self.af != AddressFamily::Inet {
+            return Err(SocketError::Other(error::EAFNOSUPPORT));
+        }
+        let len = self.recv(&mut [], MsgFlags::PEEK | MsgFlags::TRUNC)?;
+        let mut data = vec![0u8; len as usize];
+        let mut addr = InetSockAddr::default();
+        let mut slen = size_of::<InetSockAddr>() as u32;
+        let got = self.recvfrom_raw(
+            &mut data,
+            MsgFlags::NONE,
+            &mut addr as *mut InetSockAddr as *mut c_void,
+            &mut slen,
+        )?;
+        data.truncate(got as usize);
+        Ok((data, addr))
+    }
+
+    fn recvfrom_raw(
+        &self,
+        buffer: &mut [u8],
+        flags: MsgFlags,
+        addr: *mut c_void,
+        slen: &mut u32,
+    ) -> Result<i64, SocketError> {
+        self.with_read_deadline(flags.0 & MsgFlags::DONTWAIT.0 != 0, || unsafe {
+            recvfrom(self.fd, buffer.as_mut_ptr(), buffer.len() as u64, flags.0, addr, &mut *slen)
+        })
+    }
+
+    /// Receive into `buffer` without removing the data from the queue, so
+    /// the next read sees it again.
+    pub fn peek(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        self.recv(buffer, MsgFlags::PEEK)
+    }
+
+    /// Whether a write can go ahead without blocking, i.e. the send buffer
+    /// has room, polling for `POLLOUT`. `None` only looks at the current
+    /// state; `Some(timeout)` waits up to `timeout` for room to appear.
+    ///
+    /// A socket with a pending error, or shut down for writing, counts as
+    /// writable too: a write there fails at once rather than blocking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError};
+    /// use std::time::Duration;
+    ///
+    /// let (mut client, _server) = Socket::tcp_loopback_pair().unwrap();
+    /// assert!(client.is_writable(None).unwrap());
+    ///
+    /// // Fill the send buffer (and the peer's receive buffer) without blocking.
+    /// client.nonblock();
+    /// let chunk = [0u8; 65536];
+    /// while client.write(&chunk) != Err(SocketError::WouldBlock) {}
+    /// assert!(!client.is_writable(None).unwrap());
+    /// assert!(!client.is_writable(Some(Duration::from_millis(20))).unwrap());
+    /// ```
+    pub fn is_writable(&self, timeout: Option<Duration>) -> Result<bool, SocketError> {
+        let deadline = Instant::now() + timeout.unwrap_or(Duration::ZERO);
+        loop {
+            match safe::safe_poll_one(self.fd, consts::POLLOUT, poll_timeout(deadline)) {
+                r if r < 0 => match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                },
+                r => return Ok(r > 0),
+            }
+        }
+    }
+
+    /// Cheaply check whether the peer is still there, without consuming
+    /// any data and without blocking.
+    ///
+    /// This peeks a single byte with `MSG_PEEK | MSG_DONTWAIT`: pending data
+    /// or `EAGAIN` means the connection is open, a return of 0 means the
+    /// peer closed it, and any other error means it is dead. (A zero-length
+    /// peek can't be used, as it returns 0 whether or not the peer is gone.)
+    /// Unread data is reported as connected even if the peer has closed
+    /// since sending it.
+    ///
+    /// The check only sees what the kernel already knows. A peer that
+    /// vanished without sending a FIN or RST, e.g. after losing power, still
+    /// looks connected until keepalive or a failed write notices.
+    pub fn is_connected(&self) -> bool {
+        let mut byte = [0u8; 1];
+        match self.recv(&mut byte, MsgFlags::PEEK | MsgFlags::DONTWAIT) {
+            Ok(n) => n > 0,
+            Err(SocketError::WouldBlock) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Read once, waiting no later than `deadline` for data to arrive.
+    ///
+    /// Handy for one overall deadline spread over several reads: keep
+    /// passing the same `Instant` instead of working out what's left each
+    /// time. A deadline that has already passed fails with
+    /// `SocketError::TimedOut` straight away, without reading even data
+    /// that is already there. Any read timeout set on the socket is not
+    /// used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError, SocketType};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"part one").unwrap();
+    /// let deadline = Instant::now() + Duration::from_millis(200);
+    /// let mut buf = [0u8; 64];
+    /// assert_eq!(server.read_until_deadline(&mut buf, deadline).unwrap(), 8);
+    /// // Part two never comes.
+    /// assert_eq!(server.read_until_deadline(&mut buf, deadline), Err(SocketError::TimedOut));
+    /// assert!(Instant::now() >= deadline);
+    /// ```
+    pub fn read_until_deadline(&self, buffer: &mut [u8], deadline: Instant) -> Result<i64, SocketError> {
+        loop {
+            if Instant::now() >= deadline {
+                return Err(SocketError::TimedOut);
+            }
+            match safe::safe_poll_one(self.fd, consts::POLLIN, poll_timeout(deadline)) {
+                0 => return Err(SocketError::TimedOut),
+                r if r < 0 => match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                },
+                _ => {}
+            }
+            // Readiness can be spurious, so don't let the read block.
+            match self.recv(buffer, MsgFlags::DONTWAIT) {
+                Err(SocketError::WouldBlock) | Err(SocketError::Interrupted) => {}
+                r => return r,
+            }
+        }
+    }
+
+    /// Do one `read` of up to `max` bytes and return exactly what arrived
+    /// as an owned buffer. A closed connection gives an empty `Vec`.
+    ///
+    /// This allocates `max` bytes on every call. Code on a hot path should
+    /// `read` into a buffer it keeps and reuses instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"owned").unwrap();
+    /// assert_eq!(server.recv_owned(1024).unwrap(), b"owned");
+    /// drop(client);
+    /// assert!(server.recv_owned(1024).unwrap().is_empty());
+    /// ```
+    pub fn recv_owned(&self, max: usize) -> Result<Vec<u8>, SocketError> {
+        let mut buf = vec![0u8; max];
+        let len = self.read(&mut buf)?;
+        buf.truncate(len as usize);
+        Ok(buf)
+    }
+
+    /// Throw away everything that is ready to read right now, returning how
+    /// many bytes were discarded. Useful for getting back in step after a
+    /// framing error, or cleaning up a pooled connection before reuse.
+    ///
+    /// Reads use `MSG_DONTWAIT`, so this never blocks, even on a blocking
+    /// socket. It returns as soon as no more data is immediately
+    /// available (or the peer has closed), so bytes still in flight are
+    /// not waited for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"garbage").unwrap();
+    /// let mut one = [0u8; 1];
+    /// server.peek(&mut one).unwrap();
+    /// assert_eq!(server.drain().unwrap(), 7);
+    /// assert_eq!(server.drain().unwrap(), 0);
+    /// ```
+    pub fn drain(&self) -> Result<usize, SocketError> {
+        let mut scratch = [0u8; 4096];
+        let mut total = 0;
+        loop {
+            match self.recv(&mut scratch, MsgFlags::DONTWAIT) {
+                Ok(0) | Err(SocketError::WouldBlock) => return Ok(total),
+                Ok(n) => total += n as usize,
+                Err(SocketError::Interrupted) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read with `MSG_WAITALL`, blocking until `buffer` is completely
+    /// filled or the connection ends, in one call. Returns the number of
+    /// bytes read, which is short only at EOF or when the wait is cut off.
+    ///
+    /// Unlike `read`, which returns whatever has arrived, this waits for
+    /// the rest. A read timeout still applies to the whole call, though:
+    /// once it expires the bytes received so far are returned, and only a
+    /// timeout with nothing received at all is `SocketError::TimedOut`. A
+    /// signal can likewise end the wait early with the bytes so far. Use
+    /// `read_exact` to treat a short read as an error instead. On a
+    /// datagram socket `MSG_WAITALL` has no effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let (client, mut server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// let writer = std::thread::spawn(move || {
+    ///     client.write_all(b"hello ").unwrap();
+    ///     std::thread::sleep(Duration::from_millis(50));
+    ///     client.write_all(b"world").unwrap();
+    ///     client
+    /// });
+    /// let mut buf = [0u8; 11];
+    /// assert_eq!(server.read_full(&mut buf).unwrap(), 11);
+    /// assert_eq!(&buf, b"hello world");
+    ///
+    /// // A timeout returns what arrived before it.
+    /// let client = writer.join().unwrap();
+    /// server.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    /// client.write_all(b"abc").unwrap();
+    /// let mut buf = [0u8; 8];
+    /// assert_eq!(server.read_full(&mut buf).unwrap(), 3);
+    /// ```
+    pub fn read_full(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        let ret = match self.restarting(|| unsafe {
+            recv(self.fd, buffer.as_mut_ptr(), buffer.len() as u64, MsgFlags::WAITALL.0)
+        }) {
+            Err(SocketError::WouldBlock) if self.read_timeout.is_some() && !self.is_nonblocking() => {
+                Err(SocketError::TimedOut)
+            }
+            r => r,
+        };
+        #[cfg(feature = "stats")]
+        if let Ok(n) = ret {
+            self.bytes_read.fetch_add(n as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+        ret
+    }
+
+    /// Read until `buffer` is completely filled. A connection that ends
+    /// first is reported as `SocketError::UnexpectedEof`.
+    pub fn read_exact(&self, buffer: &mut [u8]) -> Result<(), SocketError> {
+        let mut done = 0;
+        while done < buffer.len() {
+            match self.read(&mut buffer[done..])? {
+                0 => return Err(SocketError::UnexpectedEof),
+                n => done += n as usize,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `buffer`, returning how many bytes were accepted, which may
+    /// be fewer than asked for.
+    ///
+    /// On a non-blocking socket whose send buffer is full, nothing is
+    /// written and `SocketError::WouldBlock` is returned; wait for
+    /// `PollEvents::OUT` before trying again. `send` behaves the same.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError, SocketType};
+    ///
+    /// let (mut client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.nonblock();
+    /// let chunk = [0u8; 65536];
+    /// let err = loop {
+    ///     if let Err(e) = client.write(&chunk) {
+    ///         break e;
+    ///     }
+    /// };
+    /// assert_eq!(err, SocketError::WouldBlock);
+    /// ```
+    pub fn write(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        let ret = self.restarting(|| unsafe { write(self.fd, buffer.as_ptr(), buffer.len() as u64) })?;
+        #[cfg(feature = "stats")]
+        self.bytes_written.fetch_add(ret as u64, std::sync::atomic::Ordering::Relaxed);
+        Ok(ret)
+    }
+
+    /// Total bytes returned by `read` and `read_full` on this socket so
+    /// far, including reads made by the helpers built on them, such as
+    /// `read_exact`. Only available with the `stats` feature.
+    ///
+    /// `recv`, `recvfrom`, `peek` and `recv_mmsg` are not counted. Neither
+    /// are reads on another descriptor for the same connection: the write
+    /// half from `into_split` starts again from 0, and the read half keeps
+    /// this socket's totals.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"twelve bytes").unwrap();
+    /// let mut buf = [0u8; 12];
+    /// server.read_exact(&mut buf).unwrap();
+    /// assert_eq!(client.bytes_written(), 12);
+    /// assert_eq!(server.bytes_read(), 12);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Total bytes accepted by `write` and `write_vectored` on this socket
+    /// so far, including writes made by the helpers built on them, such
+    /// as `write_all`. Only available with the `stats` feature.
+    ///
+    /// `send`, `sendto` and `send_mmsg` are not counted, and neither is
+    /// `write_all_timeout`, which sends with `send`. As with `bytes_read`,
+    /// the halves from `into_split` keep separate totals.
+    #[cfg(feature = "stats")]
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Send `buffer` with the given flags, returning the number of bytes
+    /// sent.
+    pub fn send(&self, buffer: &[u8], flags: MsgFlags) -> Result<i64, SocketError> {
+        self.restarting(|| unsafe { send(self.fd, buffer.as_ptr(), buffer.len() as u64, flags.0) })
+    }
+
+    /// Send `buffer` with `MSG_MORE`, telling the kernel more data follows
+    /// and it should hold this back rather than send a short segment.
+    ///
+    /// This is a per-call version of `set_cork`: issue a run of `send_more`
+    /// calls, then a plain `send` to flush. Linux only.
+    #[cfg(target_os = "linux")]
+    pub fn send_more(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        self.send(buffer, MsgFlags::MORE)
+    }
+
+    /// Send `buffer` to `dest`. Mostly useful on unconnected datagram
+    /// sockets.
+    pub fn sendto(&self, buffer: &[u8], dest: &BindFamily) -> Result<i64, SocketError> {
+        self.sendto_flags(buffer, MsgFlags::NONE, dest)
+    }
+
+    /// Resolve `host` with `resolve` and send `buffer` to the first of its
+    /// addresses in this socket's family, so an IPv4 socket skips IPv6
+    /// results and vice versa. A host with no address in the family fails
+    /// with `EAFNOSUPPORT` as `SocketError::Other`.
+    ///
+    /// The name is looked up again on every call, and only one address is
+    /// tried. Callers that need caching, or to pick among the results,
+    /// should call `resolve` themselves and then `sendto`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, Socket, SocketType};
+    ///
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(inet_addr("127.0.0.1").unwrap(), 0)).unwrap();
+    /// let tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// tx.sendto_host(b"fire and forget", "localhost", rx.local_port().unwrap()).unwrap();
+    ///
+    /// let mut buf = [0u8; 32];
+    /// assert_eq!(rx.read(&mut buf).unwrap(), 15);
+    /// ```
+    pub fn sendto_host(&self, buffer: &[u8], host: &str, port: u16) -> Result<i64, SocketError> {
+        let dest = resolve(host, port)?
+            .into_iter()
+            .find(|a| a.family() == self.af)
+            .ok_or(SocketError::Other(error::EAFNOSUPPORT))?;
+        self.sendto(buffer, &dest)
+    }
+
+    /// Receive up to `bufs.len()` datagrams with one `recvmmsg(2)` call,
+    /// storing each sender in the matching `addrs` entry. Returns the number
+    /// received; only the first `min(bufs.len(), addrs.len())` slots are used.
+    ///
+    /// This blocks (subject to the read timeout) for the first datagram
+    /// only, then takes whatever else is already queued. Each filled entry
+    /// of `bufs` is shortened to the length of its datagram, so
+    /// `bufs[i].len()` is how many bytes arrived and `bufs[i]` is the
+    /// payload; entries past the returned count are left as they were.
+    /// Datagrams longer than their buffer are truncated. Linux-only, IPv4.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, InetSockAddr, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let port = rx.local_port().unwrap();
+    /// let dest = || InetSockAddr::from_str_port("127.0.0.1", port).unwrap();
+    /// let mut tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// tx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let bufs: [&[u8]; 3] = [b"one", b"two", b"three"];
+    /// assert_eq!(tx.send_mmsg(&bufs, &[dest(), dest(), dest()]).unwrap(), 3);
+    ///
+    /// let mut storage = [[0u8; 16]; 4];
+    /// let mut bufs: Vec<&mut [u8]> = storage.iter_mut().map(|b| &mut b[..]).collect();
+    /// let mut addrs: [InetSockAddr; 4] = Default::default();
+    /// assert_eq!(rx.recv_mmsg(&mut bufs, &mut addrs).unwrap(), 3);
+    /// assert_eq!(&*bufs[0], b"one");
+    /// assert_eq!(&*bufs[2], b"three");
+    /// assert_eq!(bufs[3].len(), 16);
+    /// assert_eq!(u16::from_be(addrs[1].port), tx.local_port().unwrap());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn recv_mmsg(&self, bufs: &mut [&mut [u8]], addrs: &mut [InetSockAddr]) -> Result<usize, SocketError> {
+        let mut lens = vec![0usize; bufs.len().min(addrs.len())];
+        let r = safe::safe_recvmmsg(self.fd, bufs, addrs, &mut lens, consts::MSG_WAITFORONE);
+        if r < 0 {
+            return Err(SocketError::last());
+        }
+        for (buf, &len) in bufs.iter_mut().zip(&lens[..r as usize]) {
+            let full = std::mem::take(buf);
+            let len = len.min(full.len());
+            *buf = &mut full[..len];
+        }
+        Ok(r as usize)
+    }
+
+    /// Send each of `bufs` as a datagram to the matching `dests` entry with
+    /// one `sendmmsg(2)` call, returning how many were sent. Only the first
+    /// `min(bufs.len(), dests.len())` are attempted, and a short count means
+    /// the rest were not sent; an error is returned only if none were.
+    /// Linux-only, IPv4.
+    ///
+    /// See `recv_mmsg` for an example.
+    #[cfg(target_os = "linux")]
+    pub fn send_mmsg(&self, bufs: &[&[u8]], dests: &[InetSockAddr]) -> Result<usize, SocketError> {
+        let r = safe::safe_sendmmsg(self.fd, bufs, dests, 0);
+        if r < 0 {
+            return Err(SocketError::last());
+        }
+        Ok(r as usize)
+    }
+
+    /// Send a zero-length datagram to `dest`, as used for heartbeats by
+    /// some UDP protocols.
+    ///
+    /// An empty datagram is a real packet: the peer's `recvfrom` returns 0
+    /// bytes along with our address. On a connected datagram socket
+    /// `write(&[])` and `send(&[], ..)` send one too. On a stream socket
+    /// all three send nothing, and a 0 from `read` there means EOF instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, MsgFlags, SockAddr, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut rx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let mut tx = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// tx.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let (rx_port, tx_port) = (rx.local_port().unwrap(), tx.local_port().unwrap());
+    ///
+    /// tx.send_empty(&BindFamily::Inet(lo, rx_port)).unwrap();
+    /// tx.connect(BindFamily::Inet(lo, rx_port)).unwrap();
+    /// assert_eq!(tx.write(&[]).unwrap(), 0);
+    ///
+    /// let mut buf = [0u8; 16];
+    /// for _ in 0..2 {
+    ///     let (len, from) = rx.recvfrom(&mut buf, MsgFlags::NONE).unwrap();
+    ///     assert_eq!(len, 0);
+    ///     match from {
+    ///         SockAddr::Inet(a) => assert_eq!(u16::from_be(a.port), tx_port),
+    ///         _ => unreachable!(),
+    ///     }
+    /// }
+    /// ```
+    pub fn send_empty(&self, dest: &BindFamily) -> Result<(), SocketError> {
+        self.sendto(&[], dest).map(|_| ())
+    }
+
+    fn sendto_flags(&self, buffer: &[u8], flags: MsgFlags, dest: &BindFamily) -> Result<i64, SocketError> {
+        self.restarting(|| safe::safe_sendto(self.fd, buffer, flags.0, dest))
+    }
+
+    /// Write all of `buffer`, retrying short writes. Writes interrupted by
+    /// a signal are retried as `write` retries them, according to
+    /// `set_restart_on_interrupt`.
+    pub fn write_all(&self, buffer: &[u8]) -> Result<(), SocketError> {
+        let mut done = 0;
+        while done < buffer.len() {
+            done += self.write(&buffer[done..])? as usize;
+        }
+        Ok(())
+    }
+
+    /// Write `bufs` one after the other with a single `writev`, returning
+    /// the number of bytes written. Like `write`, this may write only part
+    /// of the data, which can end in the middle of any buffer. At most 1024
+    /// buffers are written per call.
+    pub fn write_vectored(&self, bufs: &[&[u8]]) -> Result<i64, SocketError> {
+        let ret = self.restarting(|| safe::safe_writev(self.fd, bufs))?;
+        #[cfg(feature = "stats")]
+        self.bytes_written.fetch_add(ret as u64, std::sync::atomic::Ordering::Relaxed);
+        Ok(ret)
+    }
+
+    /// Write all of `bufs`, in order, with as few `writev` calls as the
+    /// kernel allows: the scatter-gather `write_all`.
+    ///
+    /// After a short write the buffers already sent are skipped and the
+    /// one it stopped in is resumed where it left off. Interrupted writes
+    /// follow `set_restart_on_interrupt`, like `write_vectored`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// // Large enough that the writes come back short.
+    /// let header = b"len=3000001\n";
+    /// let body = vec![7u8; 3_000_000];
+    /// let reader = std::thread::spawn(move || {
+    ///     let mut got = vec![0u8; 12 + 3_000_000 + 1];
+    ///     server.read_exact(&mut got).unwrap();
+    ///     got
+    /// });
+    /// client.write_all_vectored(&[header, &[], &body, b"!"]).unwrap();
+    /// let got = reader.join().unwrap();
+    /// assert_eq!(&got[..12], header);
+    /// assert!(got[12..12 + 3_000_000].iter().all(|&b| b == 7));
+    /// assert_eq!(got.last(), Some(&b'!'));
+    /// ```
+    pub fn write_all_vectored(&self, bufs: &[&[u8]]) -> Result<(), SocketError> {
+        let mut left: Vec<&[u8]> = bufs.iter().copied().filter(|b| !b.is_empty()).collect();
+        let mut first = 0;
+        while first < left.len() {
+            let mut n = self.write_vectored(&left[first..])? as usize;
+            while first < left.len() && n >= left[first].len() {
+                n -= left[first].len();
+                first += 1;
+            }
+            if n > 0 {
+                left[first] = &left[first][n..];
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all of `buffer` within `timeout`, however slowly the peer
+    /// reads.
+    ///
+    /// Each chunk waits for room in the send buffer first, and all of them
+    /// share one deadline. If it passes, the result is
+    /// `SocketError::WriteTimedOut` with how many bytes did go out, so the
+    /// caller knows where the stream was cut off.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let (client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all_timeout(b"hello", Duration::from_secs(1)).unwrap();
+    ///
+    /// // Far more than the socket buffers hold, and nobody reads it.
+    /// let big = vec![0u8; 64 << 20];
+    /// match client.write_all_timeout(&big, Duration::from_millis(200)) {
+    ///     Err(SocketError::WriteTimedOut(n)) => assert!(n < big.len()),
+    ///     r => panic!("{:?}", r),
+    /// }
+    /// ```
+    pub fn write_all_timeout(&self, buffer: &[u8], timeout: Duration) -> Result<(), SocketError> {
+        let deadline = Instant::now() + timeout;
+        let mut done = 0;
+        while done < buffer.len() {
+            if Instant::now() >= deadline {
+                return Err(SocketError::WriteTimedOut(done));
+            }
+            match safe::safe_poll_one(self.fd, consts::POLLOUT, poll_timeout(deadline)) {
+                0 => return Err(SocketError::WriteTimedOut(done)),
+                r if r < 0 => match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                },
+                _ => {}
+            }
+            match self.send(&buffer[done..], MsgFlags::DONTWAIT) {
+                Ok(n) => done += n as usize,
+                Err(SocketError::WouldBlock) | Err(SocketError::Interrupted) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `data` as one frame: a 4-byte big-endian length followed by
+    /// the payload. Payloads that don't fit a `u32` length are rejected with
+    /// `SocketError::MessageTooLarge`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketError, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut server = Socket::listen_inet(lo, 0, None).unwrap();
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, server.local_port().unwrap())).unwrap();
+    /// let (peer, _) = server.acceptinet().unwrap();
+    ///
+    /// client.write_frame(b"hello").unwrap();
+    /// client.write_frame(b"far too long").unwrap();
+    /// assert_eq!(peer.read_frame(64).unwrap(), b"hello");
+    /// assert_eq!(peer.read_frame(8), Err(SocketError::MessageTooLarge));
+    /// ```
+    pub fn write_frame(&self, data: &[u8]) -> Result<(), SocketError> {
+        let len = u32::try_from(data.len()).map_err(|_| SocketError::MessageTooLarge)?;
+        self.write_all(&len.to_be_bytes())?;
+        self.write_all(data)
+    }
+
+    /// Read one frame written by `write_frame` and return its payload.
+    ///
+    /// A frame announcing more than `max` bytes fails with
+    /// `SocketError::MessageTooLarge` before anything is allocated. Its
+    /// payload is left unread, so the stream is out of sync afterwards.
+    pub fn read_frame(&self, max: usize) -> Result<Vec<u8>, SocketError> {
+        let mut len = [0u8; 4];
+        self.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > max {
+            return Err(SocketError::MessageTooLarge);
+        }
+        let mut data = vec![0u8; len];
+        self.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Shut down the read side, the write side, or both sides of the
+    /// connection. Unlike `close`, this affects every descriptor that
+    /// refers to the socket.
+    pub fn shutdown(&self, how: Shutdown) -> Result<(), SocketError> {
+        if safe::safe_shutdown(self.fd, how) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Close a TCP connection cleanly.
+    ///
+    /// Closing a socket that still has unread data makes the kernel send a
+    /// RST instead of a FIN, which can destroy data the peer hasn't read
+    /// yet. This shuts down the write side, reads until the peer closes its
+    /// side too (or `drain_timeout` runs out), then closes the socket.
+    ///
+    /// Anything the peer sends in the meantime is read and thrown away.
+    /// The socket is closed in every case. `SocketError::TimedOut` means
+    /// the peer didn't finish within `drain_timeout`.
+    pub fn graceful_shutdown(&mut self, drain_timeout: Duration) -> Result<(), SocketError> {
+        let ret = self.drain_until_eof(drain_timeout);
+        self.close();
+        ret
+    }
+
+    fn drain_until_eof(&self, timeout: Duration) -> Result<(), SocketError> {
+        self.shutdown(Shutdown::Write)?;
+        let deadline = Instant::now() + timeout;
+        let mut scratch = [0u8; 4096];
+        loop {
+            let ready = safe::safe_poll_one(self.fd, consts::POLLIN, poll_timeout(deadline));
+            match ready {
+                0 => return Err(SocketError::TimedOut),
+                r if r < 0 => match SocketError::last() {
+                    SocketError::Interrupted => continue,
+                    e => return Err(e),
+                },
+                _ => {}
+            }
+            match self.recv(&mut scratch, MsgFlags::DONTWAIT) {
+                Ok(0) => return Ok(()),
+                Ok(_) | Err(SocketError::WouldBlock) | Err(SocketError::Interrupted) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Split a connected socket into halves that can be moved to different
+    /// threads.
+    ///
+    /// The write half gets its own `dup`ed descriptor, so either half may be
+    /// dropped while the other stays usable. The connection is closed once
+    /// both halves are gone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut server = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// server.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// server.listen(1);
+    /// let mut client = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// client.connect(BindFamily::Inet(lo, server.local_port().unwrap())).unwrap();
+    /// let (peer, _) = server.acceptinet().unwrap();
+    ///
+    /// let (rd, wr) = client.into_split().unwrap();
+    /// let writer = std::thread::spawn(move || wr.write_all(b"ping").unwrap());
+    /// let mut buf = [0u8; 4];
+    /// peer.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"ping");
+    /// writer.join().unwrap();
+    ///
+    /// // Only the write half is gone, so the read half still works.
+    /// peer.write_all(b"pong").unwrap();
+    /// rd.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"pong");
+    /// ```
+    pub fn into_split(self) -> Result<(ReadHalf, WriteHalf), SocketError> {
+        let fd = safe::safe_dup(self.fd);
+        if fd < 0 {
+            return Err(SocketError::last());
+        }
+        let write = Socket::from_fd(fd, self.af);
+        Ok((split::ReadHalf::new(self), split::WriteHalf::new(write)))
+    }
+
+    /// Wrap the socket so that reads stop, as if at EOF, once `limit`
+    /// bytes have been read, like `std::io::Read::take`. A simple guard
+    /// against peers that send without end. See `TakeSocket`.
+    pub fn take(self, limit: u64) -> TakeSocket {
+        take::TakeSocket::new(self, limit)
+    }
+
+    pub fn setblocking(&mut self, block: bool) {
+        unsafe {
+            let flags = fcntl(self.fd, consts::F_GETFL, 0);
+            fcntl(
+                self.fd,
+                consts::F_SETFL,
+                if block {
+                    flags & !consts::O_NONBLOCK
+                } else {
+                    flags | consts::O_NONBLOCK
+                },
+            );
+        }
+    }
+
+    /// Whether the underlying descriptor supports seeking. The kernel
+    /// rejects `lseek` on a socket with `ESPIPE`, so this is false for any
+    /// real socket. It only matters for descriptors of unknown origin.
+    pub fn is_seekable(&self) -> bool {
+        const SEEK_CUR: c_int = 1;
+        unsafe { lseek(self.fd, 0, SEEK_CUR) >= 0 }
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        unsafe { fcntl(self.fd, consts::F_GETFL, 0) & consts::O_NONBLOCK != 0 }
+    }
+
+    pub fn block(&mut self) {
+        self.setblocking(true);
+    }
+
+    pub fn nonblock(&mut self) {
+        self.setblocking(false);
+    }
+
+    /// Query the kernel for the address family of this socket (`SO_DOMAIN`).
+    ///
+    /// Values the crate has no name for come back as `Unknown(raw)`, which
+    /// also applies to `kind` and `protocol`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, IpProto, Socket, SocketType};
+    ///
+    /// let sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// assert_eq!(sock.domain().unwrap(), AddressFamily::Inet);
+    /// assert_eq!(sock.kind().unwrap(), SocketType::Stream);
+    /// // The kernel reports the protocol it actually picked for IPPROTO_IP.
+    /// assert_eq!(sock.protocol().unwrap(), IpProto::Tcp);
+    /// ```
+    pub fn domain(&self) -> Result<AddressFamily, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_DOMAIN)
+            .map(AddressFamily::from_raw)
+    }
+
+    /// Query the kernel for the type of this socket (`SO_TYPE`).
+    pub fn kind(&self) -> Result<SocketType, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_TYPE)
+            .map(SocketType::from_raw)
+    }
+
+    /// Query the kernel for the protocol of this socket (`SO_PROTOCOL`).
+    pub fn protocol(&self) -> Result<IpProto, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_PROTOCOL)
+            .map(IpProto::from_raw)
+    }
+
+    /// Cork the connection so that small writes are held back and sent as
+    /// full segments (`TCP_CORK` on Linux, `TCP_NOPUSH` on the BSDs).
+    ///
+    /// The intended pattern is cork, issue several writes, then uncork.
+    /// Uncorking flushes whatever is still queued. While corked the option
+    /// takes precedence over `TCP_NODELAY`, which only comes back into
+    /// effect after uncorking. Linux also flushes corked data on its own
+    /// once it has been held for about 200ms, so corking is not a way to
+    /// delay data indefinitely.
+    pub fn set_cork(&mut self, on: bool) -> Result<(), SocketError> {
+        #[cfg(target_os = "linux")]
+        let name = consts::TCP_CORK;
+        #[cfg(not(target_os = "linux"))]
+        let name = consts::TCP_NOPUSH;
+        self.setsockopt_int(consts::IPPROTO_TCP, name, on as c_int)
+    }
+
+    /// Enable TCP Fast Open on a listener, letting clients send data in
+    /// the SYN. `qlen` caps how many Fast Open requests may be pending
+    /// before the handshake completes.
+    ///
+    /// The kernel only accepts them when the server bit (2) of the
+    /// `net.ipv4.tcp_fastopen` sysctl is set, e.g. `sysctl -w
+    /// net.ipv4.tcp_fastopen=3` for both client and server. Linux only.
+    #[cfg(target_os = "linux")]
+    pub fn set_fastopen(&mut self, qlen: i32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_FASTOPEN, qlen)
+    }
+
+    /// Limit how many times the kernel retransmits the SYN of a `connect`
+    /// before giving up with `SocketError::TimedOut` (`TCP_SYNCNT`), so the
+    /// kernel itself bounds the connect instead of `connect_timeout`.
+    ///
+    /// The first retransmit goes out after about a second and each wait
+    /// doubles, so `count` retries give up after roughly `2^(count+1) - 1`
+    /// seconds: 3s for 1, 7s for 2, 15s for 3, and 127s for the usual
+    /// default of 6. `count` must be 1 to 255. Linux only.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// sock.set_syn_retries(2).unwrap();
+    /// assert_eq!(sock.syn_retries().unwrap(), 2);
+    /// assert!(sock.set_syn_retries(0).is_err());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_syn_retries(&mut self, count: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_SYNCNT, count.min(i32::MAX as u32) as c_int)
+    }
+
+    /// The SYN retransmit limit; see `set_syn_retries`.
+    #[cfg(target_os = "linux")]
+    pub fn syn_retries(&self) -> Result<u32, SocketError> {
+        self.getsockopt_int(consts::IPPROTO_TCP, consts::TCP_SYNCNT).map(|v| v as u32)
+    }
+
+    /// Hold new connections back from `accept` on this listener until the
+    /// client has sent data (`TCP_DEFER_ACCEPT`), waiting at most about
+    /// `secs` seconds. 0 turns it off.
+    ///
+    /// A connection accepted with this set has request bytes waiting,
+    /// unless the timeout elapsed first: the kernel then stops waiting and
+    /// hands over (or drops) the silent connection, so the first `read` of
+    /// an accepted socket can still block or return 0. The kernel rounds
+    /// `secs` up to a whole number of SYN-ACK retransmissions. Linux only.
+    #[cfg(target_os = "linux")]
+    pub fn set_defer_accept(&mut self, secs: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_DEFER_ACCEPT, secs.min(i32::MAX as u32) as c_int)
+    }
+
+    /// Connect to `bf` with TCP Fast Open, sending `initial_data` along
+    /// with the SYN. Returns how much of `initial_data` was sent.
+    ///
+    /// This needs the client bit (1) of the `net.ipv4.tcp_fastopen` sysctl,
+    /// which is on by default. The first connection to a server only
+    /// fetches a Fast Open cookie and sends the data after the handshake.
+    /// Later ones carry it in the SYN. Linux only.
+    #[cfg(target_os = "linux")]
+    pub fn connect_fastopen(&mut self, bf: BindFamily, initial_data: &[u8]) -> Result<i64, SocketError> {
+        self.sendto_flags(initial_data, MsgFlags::FASTOPEN, &bf)
+    }
+
+    /// Set the kernel's queueing priority for packets sent on this socket
+    /// (`SO_PRIORITY`). Queueing disciplines use this independently of the
+    /// ToS bits.
+    ///
+    /// Priorities 0 through 6 are open to everyone. Anything higher needs
+    /// `CAP_NET_ADMIN` and fails with `SocketError::PermissionDenied`
+    /// otherwise. Values past `i32::MAX` are clamped to it.
+    #[cfg(target_os = "linux")]
+    pub fn set_priority(&mut self, prio: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_PRIORITY, prio.min(i32::MAX as u32) as c_int)
+    }
+
+    /// Get the current `SO_PRIORITY` of this socket.
+    #[cfg(target_os = "linux")]
+    pub fn priority(&self) -> Result<u32, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_PRIORITY)
+            .map(|p| p as u32)
+    }
+
+    /// The maximum segment size of this TCP connection (`TCP_MAXSEG`), for
+    /// sizing writes to whole segments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketType};
+    ///
+    /// let (client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// assert!(client.mss().unwrap() > 0);
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// sock.set_mss(1200).unwrap();
+    /// ```
+    pub fn mss(&self) -> Result<u32, SocketError> {
+        self.getsockopt_int(consts::IPPROTO_TCP, consts::TCP_MAXSEG)
+            .map(|m| m as u32)
+    }
+
+    /// Clamp the maximum segment size. Only has an effect before
+    /// `connect` or `listen`. It is an upper bound for the handshake, so
+    /// the kernel may still settle on something smaller, and it rejects
+    /// values outside the range it allows.
+    pub fn set_mss(&mut self, mss: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_MAXSEG, mss as c_int)
+    }
+
+    /// Kernel statistics for this TCP connection (`TCP_INFO`): round trip
+    /// time, congestion window, retransmits and so on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// let info = client.tcp_info().unwrap();
+    /// assert_eq!(info.state, 1); // TCP_ESTABLISHED
+    /// println!("rtt {}us, cwnd {}", info.rtt, info.snd_cwnd);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn tcp_info(&self) -> Result<TcpInfo, SocketError> {
+        let mut buf = [0u8; 256];
+        let (r, len) = safe::safe_getsockopt_buf(self.fd, consts::IPPROTO_TCP, consts::TCP_INFO, &mut buf);
+        if r < 0 {
+            Err(SocketError::last())
         } else {
-            Ok(Self { fd: ws, af: family })
+            Ok(TcpInfo::from_bytes(&buf[..len]))
+        }
+    }
+
+    /// Choose the TCP congestion control algorithm (`TCP_CONGESTION`),
+    /// such as `"cubic"`, `"reno"` or `"bbr"`, for example to force BBR on a
+    /// long, fat link.
+    ///
+    /// The kernel loads the algorithm's module on demand if it can. An
+    /// algorithm that isn't available fails with `SocketError::Unsupported`;
+    /// one that is available but not listed in
+    /// `net.ipv4.tcp_allowed_congestion_control` needs `CAP_NET_ADMIN`,
+    /// failing with `SocketError::PermissionDenied` without it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketError, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// let current = sock.congestion().unwrap();
+    /// assert!(!current.is_empty());
+    /// sock.set_congestion(&current).unwrap();
+    /// assert_eq!(sock.set_congestion("no-such-algo"), Err(SocketError::Unsupported));
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_congestion(&mut self, algo: &str) -> Result<(), SocketError> {
+        if safe::safe_setsockopt_buf(self.fd, consts::IPPROTO_TCP, consts::TCP_CONGESTION, algo.as_bytes()) < 0 {
+            return Err(match SocketError::last() {
+                SocketError::Other(error::ENOENT) => SocketError::Unsupported,
+                e => e,
+            });
         }
+        Ok(())
     }
 
-    pub fn bind(&mut self, bf: BindFamily) -> Result<(), i32> {
-        let r = safe::safe_bind(self.fd, bf);
+    /// The TCP congestion control algorithm in use, as set by
+    /// `set_congestion` or the system default.
+    #[cfg(target_os = "linux")]
+    pub fn congestion(&self) -> Result<String, SocketError> {
+        let mut buf = [0u8; consts::TCP_CA_NAME_MAX];
+        let (r, len) = safe::safe_getsockopt_buf(self.fd, consts::IPPROTO_TCP, consts::TCP_CONGESTION, &mut buf);
         if r < 0 {
-            Err(r)
+            return Err(SocketError::last());
+        }
+        let name = &buf[..len];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(len);
+        Ok(String::from_utf8_lossy(&name[..end]).into_owned())
+    }
+
+    /// The destination an accepted IPv4 connection was originally sent to,
+    /// before an iptables `REDIRECT`/`DNAT` rule sent it here
+    /// (`SO_ORIGINAL_DST`). This is what a transparent proxy connects on to.
+    ///
+    /// The answer comes from netfilter connection tracking. Without the
+    /// conntrack module loaded this fails with `SocketError::Unsupported`;
+    /// for a connection that was not redirected it fails with
+    /// `SocketError::Other(ENOENT)`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mzsocket::Socket;
+    ///
+    /// // iptables -t nat -A OUTPUT -p tcp --dport 80 -j REDIRECT --to-ports 47127
+    /// let mut listener = Socket::listen_inet(0, 47127, None).unwrap();
+    /// let (conn, _) = listener.accept().unwrap();
+    /// let dst = conn.original_dst().unwrap();
+    /// println!("was headed for port {}", u16::from_be(dst.port));
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn original_dst(&self) -> Result<InetSockAddr, SocketError> {
+        let mut addr = InetSockAddr::default();
+        if safe::safe_getsockopt(self.fd, consts::SOL_IP, consts::SO_ORIGINAL_DST, &mut addr) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(addr)
+        }
+    }
+
+    /// The IPv6 counterpart of `original_dst` (`IP6T_SO_ORIGINAL_DST`).
+    #[cfg(target_os = "linux")]
+    pub fn original_dst6(&self) -> Result<Inet6SockAddr, SocketError> {
+        let mut addr = Inet6SockAddr::default();
+        if safe::safe_getsockopt(self.fd, consts::SOL_IPV6, consts::SO_ORIGINAL_DST, &mut addr) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(addr)
+        }
+    }
+
+    /// Set the receive buffer to `bytes`, even above the
+    /// `net.core.rmem_max` cap, so bursts of large datagrams aren't
+    /// dropped (`SO_RCVBUFFORCE`).
+    ///
+    /// Going over the cap needs `CAP_NET_ADMIN`. Without it the buffer is
+    /// still set with plain `SO_RCVBUF`, which the kernel clamps to
+    /// `rmem_max`, and `SocketError::PermissionDenied` is returned so the
+    /// caller knows it got less than it asked for. As with `SO_RCVBUF`,
+    /// the kernel doubles the value to leave room for bookkeeping.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketError, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// match sock.set_recv_buffer_force(4 << 20) {
+    ///     Ok(()) => assert!(sock.recv_buffer().unwrap() >= 4 << 20),
+    ///     Err(SocketError::PermissionDenied) => println!("capped at {}", sock.recv_buffer().unwrap()),
+    ///     Err(e) => panic!("{}", e),
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_recv_buffer_force(&mut self, bytes: i32) -> Result<(), SocketError> {
+        match self.setsockopt_int(consts::SOL_SOCKET, consts::SO_RCVBUFFORCE, bytes) {
+            Err(SocketError::PermissionDenied) => {
+                self.setsockopt_int(consts::SOL_SOCKET, consts::SO_RCVBUF, bytes)?;
+                Err(SocketError::PermissionDenied)
+            }
+            r => r,
+        }
+    }
+
+    /// The size of the receive buffer in bytes (`SO_RCVBUF`), as doubled
+    /// by the kernel.
+    pub fn recv_buffer(&self) -> Result<i32, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_RCVBUF)
+    }
+
+    /// Set or clear the Don't Fragment bit on everything this socket
+    /// sends (`IP_MTU_DISCOVER`, or `IPV6_MTU_DISCOVER` on IPv6 sockets,
+    /// which control whether routers may fragment).
+    ///
+    /// With it set, a datagram larger than the known path MTU fails
+    /// straight away with `SocketError::MessageTooLarge`. When a router
+    /// further along reports a smaller MTU, the kernel lowers
+    /// `path_mtu` and, once `recv_error` has enabled the error queue,
+    /// queues an `EMSGSIZE` error whose `info` is the new MTU. That is all
+    /// a path MTU probe needs. With it cleared the kernel fragments as
+    /// needed and never reports the MTU.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut peer = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// peer.bind(BindFamily::Inet(lo, 0)).unwrap();
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// sock.connect(BindFamily::Inet(lo, peer.local_port().unwrap())).unwrap();
+    /// sock.set_df(true).unwrap();
+    /// assert!(sock.path_mtu().unwrap() >= 576);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_df(&mut self, on: bool) -> Result<(), SocketError> {
+        let mode = if on { consts::IP_PMTUDISC_DO } else { consts::IP_PMTUDISC_DONT };
+        match self.af {
+            AddressFamily::Inet6 => self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_MTU_DISCOVER, mode),
+            _ => self.setsockopt_int(consts::SOL_IP, consts::IP_MTU_DISCOVER, mode),
+        }
+    }
+
+    /// The path MTU the kernel currently knows for the peer of this
+    /// connected socket (`IP_MTU`/`IPV6_MTU`). Fails with `ENOTCONN` on a
+    /// socket that isn't connected.
+    #[cfg(target_os = "linux")]
+    pub fn path_mtu(&self) -> Result<u32, SocketError> {
+        let mtu = match self.af {
+            AddressFamily::Inet6 => self.getsockopt_int(consts::SOL_IPV6, consts::IPV6_MTU)?,
+            _ => self.getsockopt_int(consts::SOL_IP, consts::IP_MTU)?,
+        };
+        Ok(mtu as u32)
+    }
+
+    /// The MTU of the interface that owns this socket's local address,
+    /// found through `interface_addrs` and `interface_mtu_by_name`.
+    ///
+    /// This is the limit of the first hop only. The path MTU, from
+    /// `path_mtu`, is the smallest MTU of every link to the peer, which may
+    /// be lower (a tunnel or VPN further along, say), and is what a
+    /// datagram must fit to cross without fragmenting; it is only known
+    /// for a connected socket, while this works as soon as there is a
+    /// local address. A socket bound to the wildcard address and not
+    /// connected has no single interface and fails with `EADDRNOTAVAIL`,
+    /// as does one whose address no interface owns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, interface_addrs, interface_mtu_by_name, AddressFamily, BindFamily, IfFlags, Socket, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// sock.bind(BindFamily::Inet(inet_addr("127.0.0.1").unwrap(), 0)).unwrap();
+    /// let addrs = interface_addrs().unwrap();
+    /// let lo = addrs.iter().find(|a| a.flags.contains(IfFlags::LOOPBACK)).unwrap();
+    /// assert_eq!(sock.interface_mtu().unwrap(), interface_mtu_by_name(&lo.name).unwrap());
+    ///
+    /// let unbound = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// assert!(unbound.interface_mtu().is_err());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn interface_mtu(&self) -> Result<u32, SocketError> {
+        let local = match self.sockname()? {
+            SockAddr::Inet(a) => BindFamily::Inet(u32::from_be_bytes(unsafe { a.addr.addr8 }), 0),
+            SockAddr::Inet6(a) => BindFamily::Inet6Scoped(u128::from_be_bytes(unsafe { a.addr.addr8 }), 0, a.scopeid),
+            _ => return Err(SocketError::Other(error::EAFNOSUPPORT)),
+        };
+        // Link-local addresses can repeat across interfaces, so the scope
+        // has to match too when the socket has one.
+        let same_ip = |addr: &BindFamily| match (addr, &local) {
+            (BindFamily::Inet(a, _), BindFamily::Inet(b, _)) => a == b,
+            (BindFamily::Inet6(a, _), BindFamily::Inet6Scoped(b, _, _)) => a == b,
+            (BindFamily::Inet6Scoped(a, _, sa), BindFamily::Inet6Scoped(b, _, sb)) => a == b && (*sb == 0 || sa == sb),
+            _ => false,
+        };
+        let unspecified = matches!(local, BindFamily::Inet(0, _) | BindFamily::Inet6Scoped(0, _, _));
+        match interface_addrs()?.into_iter().find(|a| !unspecified && same_ip(&a.addr)) {
+            Some(ifa) => interface_mtu_by_name(&ifa.name),
+            None => Err(SocketError::Other(error::EADDRNOTAVAIL)),
+        }
+    }
+
+    /// Join the source-specific multicast channel (`source`, `group`) on
+    /// the interface with address `interface`, or let the kernel pick
+    /// with 0 (`IP_ADD_SOURCE_MEMBERSHIP`). Only traffic to `group` that
+    /// comes from `source` is delivered. Addresses are in host byte
+    /// order, as from `inet_addr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let group = inet_addr("232.1.1.1").unwrap();
+    /// let source = inet_addr("127.0.0.1").unwrap();
+    /// let lo = inet_addr("127.0.0.1").unwrap();
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// sock.bind(BindFamily::Inet(0, 0)).unwrap();
+    /// sock.join_source_multicast_v4(group, source, lo).unwrap();
+    /// sock.leave_source_multicast_v4(group, source, lo).unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn join_source_multicast_v4(&mut self, group: u32, source: u32, interface: u32) -> Result<(), SocketError> {
+        self.source_membership(consts::IP_ADD_SOURCE_MEMBERSHIP, group, source, interface)
+    }
+
+    /// Leave a channel joined with `join_source_multicast_v4`
+    /// (`IP_DROP_SOURCE_MEMBERSHIP`).
+    #[cfg(target_os = "linux")]
+    pub fn leave_source_multicast_v4(&mut self, group: u32, source: u32, interface: u32) -> Result<(), SocketError> {
+        self.source_membership(consts::IP_DROP_SOURCE_MEMBERSHIP, group, source, interface)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn source_membership(&self, name: c_int, group: u32, source: u32, interface: u32) -> Result<(), SocketError> {
+        let mreq = structs::IpMreqSource {
+            multiaddr: group.to_be(),
+            interface: interface.to_be(),
+            sourceaddr: source.to_be(),
+        };
+        if safe::safe_setsockopt(self.fd, consts::SOL_IP, name, &mreq) < 0 {
+            Err(SocketError::last())
         } else {
             Ok(())
         }
     }
 
-    pub fn listen(&mut self, backlog: i32) -> i32 {
-        unsafe { listen(self.fd, backlog) }
+    /// Send multicast from the interface with IPv4 address `interface`, in
+    /// host byte order, instead of the one the routing table picks
+    /// (`IP_MULTICAST_IF`). 0 goes back to the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{if_nametoindex, inet_addr, AddressFamily, Socket, SocketType};
+    ///
+    /// let mut v4 = Socket::new(AddressFamily::Inet, SocketType::DataGram, None).unwrap();
+    /// v4.set_multicast_if_v4(inet_addr("127.0.0.1").unwrap()).unwrap();
+    ///
+    /// let mut v6 = Socket::new(AddressFamily::Inet6, SocketType::DataGram, None).unwrap();
+    /// v6.set_multicast_if_v6(if_nametoindex("lo").unwrap()).unwrap();
+    /// ```
+    pub fn set_multicast_if_v4(&mut self, interface: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_IP, consts::IP_MULTICAST_IF, interface.to_be() as c_int)
     }
 
-    pub fn acceptinet(&mut self) -> Result<(Socket, InetSockAddr), i32> {
-        if self.af != AddressFamily::Inet {
-            return Err(-1);
+    /// Send IPv6 multicast from the interface with index `ifindex`
+    /// (`IPV6_MULTICAST_IF`). 0 goes back to the default.
+    pub fn set_multicast_if_v6(&mut self, ifindex: u32) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_MULTICAST_IF, ifindex as c_int)
+    }
+
+    /// Send the flow label given in `BindFamily::Inet6Flow` when
+    /// connecting (`IPV6_FLOWINFO_SEND`). Without this the kernel ignores
+    /// the label and sends 0 (or one of its own choosing).
+    ///
+    /// Linux only hands out labels it has leased to the socket, via
+    /// `IPV6_FLOWLABEL_MGR`, so with this on and a nonzero label nobody
+    /// leased, `connect` fails with `EINVAL`. The label of a connected
+    /// socket is then reported in the `flowinfo` of its peer address.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let mut rx = Socket::new(AddressFamily::Inet6, SocketType::DataGram, None).unwrap();
+    /// rx.bind(BindFamily::Inet6(1, 0)).unwrap();
+    /// let port = rx.local_port().unwrap();
+    ///
+    /// let mut tx = Socket::new(AddressFamily::Inet6, SocketType::DataGram, None).unwrap();
+    /// // Ignored, since IPV6_FLOWINFO_SEND is still off.
+    /// tx.connect(BindFamily::Inet6Flow(1, port, 0, 0xbeef)).unwrap();
+    /// tx.set_flowinfo_send(true).unwrap();
+    /// // Label 0 needs no lease.
+    /// tx.connect(BindFamily::Inet6Flow(1, port, 0, 0)).unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_flowinfo_send(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_FLOWINFO_SEND, on as c_int)
+    }
+
+    /// Allow `bind` to an IPv4 address that isn't (yet) configured on this
+    /// host (`IP_FREEBIND`), such as a floating VIP that will be assigned
+    /// later. Set it before calling `bind`. It needs no privileges.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// sock.set_freebind(true).unwrap();
+    /// // 203.0.113.0/24 is reserved for documentation, so nothing here owns it.
+    /// sock.bind(BindFamily::Inet(inet_addr("203.0.113.7").unwrap(), 0)).unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_freebind(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_IP, consts::IP_FREEBIND, on as c_int)
+    }
+
+    /// Mark the socket transparent (`IP_TRANSPARENT`), so it can bind to
+    /// non-local addresses and send with them as source, and accept
+    /// connections redirected to it by an iptables `TPROXY` rule.
+    ///
+    /// Needs `CAP_NET_ADMIN` (or `CAP_NET_RAW`); without it this fails
+    /// with `SocketError::PermissionDenied`.
+    #[cfg(target_os = "linux")]
+    pub fn set_transparent(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_IP, consts::IP_TRANSPARENT, on as c_int)
+    }
+
+    /// Tag every packet this socket sends with `mark` (`SO_MARK`), for
+    /// fwmark-based policy routing (`ip rule add fwmark ...`) and
+    /// netfilter rules.
+    ///
+    /// Needs `CAP_NET_ADMIN`; without it this fails with
+    /// `SocketError::PermissionDenied`.
+    ///
+    /// The kernel keeps the mark as a `u32`, so all 32 bits are used:
+    /// marks above `i32::MAX` go through the `int` option bit for bit and
+    /// `mark` reads them back unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketError, SocketType};
+    ///
+    /// let mut sock = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// match sock.set_mark(0x100) {
+    ///     Ok(()) => assert_eq!(sock.mark().unwrap(), 0x100),
+    ///     Err(SocketError::PermissionDenied) => println!("needs CAP_NET_ADMIN"),
+    ///     Err(e) => panic!("{}", e),
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_mark(&mut self, mark: u32) -> Result<(), SocketError> {
+        // Deliberately a bit cast, not a clamp: SO_MARK is a u32.
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_MARK, mark as c_int)
+    }
+
+    /// The `SO_MARK` of this socket, 0 if none was set.
+    #[cfg(target_os = "linux")]
+    pub fn mark(&self) -> Result<u32, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_MARK)
+            .map(|m| m as u32)
+    }
+
+    /// How many bytes are sitting in the send queue, written but not yet
+    /// sent or (for TCP) not yet acknowledged by the peer (`TIOCOUTQ`). A
+    /// number that keeps growing is a consumer that can't keep up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"queued").unwrap();
+    /// let mut one = [0u8; 1];
+    /// server.peek(&mut one).unwrap();
+    /// assert_eq!(server.recv_queue_len().unwrap(), 6);
+    /// assert!(client.send_queue_len().unwrap() <= 6);
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn send_queue_len(&self) -> Result<usize, SocketError> {
+        self.ioctl_int(consts::TIOCOUTQ)
+    }
+
+    /// How many bytes have been received but not yet read (`SIOCINQ`). On
+    /// a datagram socket this is the size of the next datagram.
+    #[cfg(target_os = "linux")]
+    pub fn recv_queue_len(&self) -> Result<usize, SocketError> {
+        self.ioctl_int(consts::SIOCINQ)
+    }
+
+    /// Whether the next byte to read is the TCP urgent (out-of-band) mark
+    /// (`SIOCATMARK`).
+    ///
+    /// A normal read stops short at the mark, so a reader can tell where
+    /// the urgent byte sent with `MsgFlags::OOB` fits into the stream.
+    /// This only means something with `SO_OOBINLINE` off (the default),
+    /// where the urgent byte itself is fetched with `recv` and
+    /// `MsgFlags::OOB`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{MsgFlags, Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.send(b"ab", MsgFlags::NONE).unwrap();
+    /// client.send(b"!", MsgFlags::OOB).unwrap();
+    /// let mut buf = [0u8; 16];
+    /// assert_eq!(server.read(&mut buf).unwrap(), 2);
+    /// assert!(server.at_oob_mark().unwrap());
+    /// assert_eq!(server.recv(&mut buf, MsgFlags::OOB).unwrap(), 1);
+    /// assert_eq!(buf[0], b'!');
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn at_oob_mark(&self) -> Result<bool, SocketError> {
+        self.ioctl_int(consts::SIOCATMARK).map(|v| v != 0)
+    }
+
+    /// Route this socket's traffic with routing table `table`, for hosts
+    /// with several uplinks.
+    ///
+    /// OpenBSD selects the table directly (`SO_RTABLE`). Linux has no
+    /// per-socket table option, so this sets `SO_MARK` to `table` and
+    /// relies on a policy rule sending marked packets to the table of the
+    /// same number, set up once per table:
+    ///
+    /// ```text
+    /// ip route add default via 192.0.2.1 table 100
+    /// ip rule add fwmark 100 table 100
+    /// ```
+    ///
+    /// On Linux this needs `CAP_NET_ADMIN` (see `set_mark`).
+    #[cfg(any(target_os = "linux", target_os = "openbsd"))]
+    pub fn set_routing_table(&mut self, table: u32) -> Result<(), SocketError> {
+        #[cfg(target_os = "linux")]
+        return self.set_mark(table);
+        #[cfg(target_os = "openbsd")]
+        return self.setsockopt_int(consts::OPENBSD_SOL_SOCKET, consts::SO_RTABLE, table as c_int);
+    }
+
+    /// Bypass the routing table and only send to hosts on directly
+    /// connected networks (`SO_DONTROUTE`). Useful for probes that must
+    /// never be forwarded off the local link.
+    pub fn set_dontroute(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_DONTROUTE, on as c_int)
+    }
+
+    /// Whether `SO_DONTROUTE` is set on this socket.
+    pub fn dontroute(&self) -> Result<bool, SocketError> {
+        self.getsockopt_int(consts::SOL_SOCKET, consts::SO_DONTROUTE)
+            .map(|v| v != 0)
+    }
+
+    /// Set how long a blocking `read` may wait for data. `None` waits
+    /// forever. A zero duration is rejected, since the kernel would treat
+    /// it as "no timeout".
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(SocketError::Other(error::EINVAL));
         }
-        let mut isaddr = InetSockAddr::default();
-        let mut slen = 0u32;
-        let ret = unsafe {
-            accept(
-                self.fd,
-                &mut isaddr as *mut InetSockAddr as *mut c_void,
-                &mut slen as *mut u32 as *mut c_uint,
-            )
-        } as usize;
-        if slen as usize != size_of::<InetSockAddr>() {
-            Err(ret as i32)
+        self.set_rcvtimeo(timeout)?;
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// Set how long a blocking write may wait for room in the send buffer
+    /// (`SO_SNDTIMEO`). `None` waits forever. A write that times out
+    /// without sending anything fails with `SocketError::WouldBlock`; one
+    /// that sent part of its data returns the short count.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(SocketError::Other(error::EINVAL));
+        }
+        let tv = TimeVal::from_duration(timeout);
+        if safe::safe_setsockopt(self.fd, consts::SOL_SOCKET, consts::SO_SNDTIMEO, &tv) < 0 {
+            Err(SocketError::last())
         } else {
-            Ok((
-                Self {
-                    fd: ret as i32,
-                    af: AddressFamily::Inet,
-                },
-                isaddr,
-            ))
+            Ok(())
         }
     }
 
-    pub fn acceptinet6(&mut self) -> Result<(Socket, Inet6SockAddr), i32> {
-        let mut isaddr = Inet6SockAddr::default();
-        let mut slen = 0u32;
-        let ret = unsafe {
-            accept(
-                self.fd,
-                &mut isaddr as *mut Inet6SockAddr as *mut c_void,
-                &mut slen as *mut u32 as *mut c_uint,
-            )
-        } as usize;
-        if slen as usize != size_of::<Inet6SockAddr>() {
-            Err(ret as i32)
+    /// The read timeout currently set on the socket (`SO_RCVTIMEO`), as
+    /// `set_read_timeout` takes it: `None` when reads wait forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let (mut client, _server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// assert_eq!(client.read_timeout().unwrap(), None);
+    /// client.set_read_timeout(Some(Duration::from_millis(1500))).unwrap();
+    /// assert_eq!(client.read_timeout().unwrap(), Some(Duration::from_millis(1500)));
+    /// client.set_write_timeout(Some(Duration::from_secs(2))).unwrap();
+    /// assert_eq!(client.write_timeout().unwrap(), Some(Duration::from_secs(2)));
+    /// client.set_read_timeout(None).unwrap();
+    /// assert_eq!(client.read_timeout().unwrap(), None);
+    /// ```
+    pub fn read_timeout(&self) -> Result<Option<Duration>, SocketError> {
+        self.getsockopt_timeout(consts::SO_RCVTIMEO)
+    }
+
+    /// The write timeout currently set on the socket (`SO_SNDTIMEO`), as
+    /// `set_write_timeout` takes it: `None` when writes wait forever.
+    pub fn write_timeout(&self) -> Result<Option<Duration>, SocketError> {
+        self.getsockopt_timeout(consts::SO_SNDTIMEO)
+    }
+
+    /// Send small writes immediately instead of coalescing them while
+    /// earlier data is unacknowledged (`TCP_NODELAY`, disabling Nagle's
+    /// algorithm).
+    pub fn set_nodelay(&mut self, on: bool) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_NODELAY, on as c_int)
+    }
+
+    /// Whether `TCP_NODELAY` is set on this socket.
+    pub fn nodelay(&self) -> Result<bool, SocketError> {
+        self.getsockopt_int(consts::IPPROTO_TCP, consts::TCP_NODELAY)
+            .map(|v| v != 0)
+    }
+
+    /// Turn TCP keepalive on, probing a connection once it has been idle
+    /// for `idle`, or off with `None` (`SO_KEEPALIVE`). The idle time is
+    /// rounded up to whole seconds. It is only adjustable on Linux
+    /// (`TCP_KEEPIDLE`); elsewhere the system default of usually two hours
+    /// applies.
+    pub fn set_keepalive(&mut self, idle: Option<Duration>) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_KEEPALIVE, idle.is_some() as c_int)?;
+        #[cfg(target_os = "linux")]
+        if let Some(idle) = idle {
+            let secs = idle.as_millis().div_ceil(1000).clamp(1, i32::MAX as u128) as c_int;
+            self.setsockopt_int(consts::IPPROTO_TCP, consts::TCP_KEEPIDLE, secs)?;
+        }
+        Ok(())
+    }
+
+    /// Read the commonly tuned options in one go, for logging how a
+    /// connection is configured. Pass the result to `apply` to copy the
+    /// configuration to another socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{AddressFamily, Socket, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let mut tuned = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// tuned.set_nodelay(true).unwrap();
+    /// tuned.set_keepalive(Some(Duration::from_secs(30))).unwrap();
+    /// tuned.set_read_timeout(Some(Duration::from_millis(1500))).unwrap();
+    /// let opts = tuned.options_snapshot().unwrap();
+    /// assert_eq!(opts.nodelay, Some(true));
+    /// assert!(opts.keepalive);
+    /// assert_eq!(opts.read_timeout, Some(Duration::from_millis(1500)));
+    /// assert_eq!(opts.write_timeout, None);
+    /// assert!(opts.ttl.is_some());
+    ///
+    /// let mut copy = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+    /// copy.apply(&opts).unwrap();
+    /// assert_eq!(copy.options_snapshot().unwrap(), opts);
+    /// ```
+    pub fn options_snapshot(&self) -> Result<SocketOptions, SocketError> {
+        let nodelay = match self.protocol()? {
+            IpProto::Tcp => Some(self.nodelay()?),
+            _ => None,
+        };
+        let ttl = match self.domain()? {
+            AddressFamily::Inet => Some(self.getsockopt_int(consts::SOL_IP, consts::IP_TTL)? as u32),
+            AddressFamily::Inet6 => Some(self.getsockopt_int(consts::SOL_IPV6, consts::IPV6_UNICAST_HOPS)? as u32),
+            _ => None,
+        };
+        Ok(SocketOptions {
+            reuse_addr: self.getsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEADDR)? != 0,
+            nodelay,
+            keepalive: self.getsockopt_int(consts::SOL_SOCKET, consts::SO_KEEPALIVE)? != 0,
+            recv_buffer: self.recv_buffer()?,
+            send_buffer: self.getsockopt_int(consts::SOL_SOCKET, consts::SO_SNDBUF)?,
+            read_timeout: self.read_timeout()?,
+            write_timeout: self.write_timeout()?,
+            ttl,
+        })
+    }
+
+    /// Set every option in `opts`, typically taken from another socket
+    /// with `options_snapshot`. The buffer sizes are halved before setting
+    /// so the kernel's doubling gives back the same numbers. `nodelay` and
+    /// `ttl` are skipped when `None`. Stops at the first option that
+    /// fails.
+    pub fn apply(&mut self, opts: &SocketOptions) -> Result<(), SocketError> {
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_REUSEADDR, opts.reuse_addr as c_int)?;
+        if let Some(on) = opts.nodelay {
+            self.set_nodelay(on)?;
+        }
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_KEEPALIVE, opts.keepalive as c_int)?;
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_RCVBUF, opts.recv_buffer / 2)?;
+        self.setsockopt_int(consts::SOL_SOCKET, consts::SO_SNDBUF, opts.send_buffer / 2)?;
+        self.set_read_timeout(opts.read_timeout)?;
+        self.set_write_timeout(opts.write_timeout)?;
+        if let Some(ttl) = opts.ttl {
+            match self.af {
+                AddressFamily::Inet6 => self.setsockopt_int(consts::SOL_IPV6, consts::IPV6_UNICAST_HOPS, ttl as c_int)?,
+                _ => self.setsockopt_int(consts::SOL_IP, consts::IP_TTL, ttl as c_int)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn getsockopt_timeout(&self, name: c_int) -> Result<Option<Duration>, SocketError> {
+        let mut tv = TimeVal::default();
+        if safe::safe_getsockopt(self.fd, consts::SOL_SOCKET, name, &mut tv) < 0 {
+            Err(SocketError::last())
         } else {
-            Ok((
-                Self {
-                    fd: ret as i32,
-                    af: AddressFamily::Inet6,
-                },
-                isaddr,
-            ))
+            Ok(tv.to_duration())
         }
     }
 
-    pub fn acceptunix(&mut self) -> Result<(Socket, UnixSockAddr), i32> {
-        let mut isaddr = UnixSockAddr::default();
-        let mut slen = 0u32;
-        let ret = unsafe {
-            accept(
-                self.fd,
-                &mut isaddr as *mut UnixSockAddr as *mut c_void,
-                &mut slen as *mut u32 as *mut c_uint,
-            )
-        } as usize;
-        if slen as usize != size_of::<UnixSockAddr>() {
-            Err(ret as i32)
+    /// Make `close` wait up to `timeout` (whole seconds) for unsent data to
+    /// go out, or with `None` return at once and let the kernel finish in
+    /// the background, the default (`SO_LINGER`).
+    ///
+    /// A zero timeout discards unsent data and resets the connection on
+    /// close, so the peer sees `SocketError::ConnectionReset` instead of
+    /// end of file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Socket, SocketError, SocketType};
+    /// use std::time::Duration;
+    ///
+    /// let (client, mut server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// server.set_linger(Some(Duration::ZERO)).unwrap();
+    /// drop(server);
+    /// let mut buf = [0u8; 16];
+    /// assert_eq!(client.read(&mut buf), Err(SocketError::ConnectionReset));
+    /// ```
+    pub fn set_linger(&mut self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        // struct linger: on/off, then seconds.
+        let linger: [c_int; 2] = match timeout {
+            Some(t) => [1, t.as_secs().min(c_int::MAX as u64) as c_int],
+            None => [0, 0],
+        };
+        if safe::safe_setsockopt(self.fd, consts::SOL_SOCKET, consts::SO_LINGER, &linger) < 0 {
+            Err(SocketError::last())
         } else {
-            Ok((
-                Self {
-                    fd: ret as i32,
-                    af: AddressFamily::Unix,
-                },
-                isaddr,
-            ))
+            Ok(())
         }
     }
 
-    pub fn connect(&mut self, bf: BindFamily) -> Result<(), i32> {
-        let r = safe::safe_connect(self.fd, bf);
-        if r < 0 {
-            Err(r)
+    fn set_rcvtimeo(&self, timeout: Option<Duration>) -> Result<(), SocketError> {
+        let tv = TimeVal::from_duration(timeout);
+        if safe::safe_setsockopt(self.fd, consts::SOL_SOCKET, consts::SO_RCVTIMEO, &tv) < 0 {
+            Err(SocketError::last())
         } else {
             Ok(())
         }
     }
 
-    pub fn read(&self, buffer: &mut [u8]) -> Result<i64, i64> {
-        unsafe {
-            let ret = read(self.fd, buffer.as_mut_ptr(), buffer.len() as u64);
-            if ret < 0 {
-                Err(ret as i64)
-            } else {
-                Ok(ret as i64)
-            }
+    #[cfg(target_os = "linux")]
+    fn ioctl_int(&self, request: u64) -> Result<usize, SocketError> {
+        let mut val: c_int = 0;
+        if safe::safe_ioctl_int(self.fd, request, &mut val) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(val as usize)
         }
     }
 
-    pub fn write(&self, buffer: &[u8]) -> Result<i64, i64> {
-        unsafe {
-            let ret = write(self.fd, buffer.as_ptr(), buffer.len() as u64);
-            if ret < 0 {
-                Err(ret as i64)
-            } else {
-                Ok(ret as i64)
-            }
+    fn getsockopt_int(&self, level: c_int, name: c_int) -> Result<c_int, SocketError> {
+        let mut val: c_int = 0;
+        if safe::safe_getsockopt(self.fd, level, name, &mut val) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(val)
         }
     }
 
-    pub fn setblocking(&mut self, block: bool) {
-        const F_GETFL: c_int = 3;
-        const F_SETFL: c_int = 4;
-        const O_NONBLOCK: c_int = 0o4000;
-        unsafe {
-            let flags = fcntl(self.fd, F_GETFL, 0);
-            fcntl(
-                self.fd,
-                F_SETFL,
-                if block {
-                    flags & !O_NONBLOCK
-                } else {
-                    flags | O_NONBLOCK
-                },
-            );
+    fn setsockopt_int(&self, level: c_int, name: c_int, val: c_int) -> Result<(), SocketError> {
+        if safe::safe_setsockopt(self.fd, level, name, &val) < 0 {
+            Err(SocketError::last())
+        } else {
+            Ok(())
         }
     }
 
-    pub fn block(&mut self) {
-        self.setblocking(true);
+    /// Close the socket. Calling this again, or dropping the socket
+    /// afterwards, does nothing.
+    ///
+    /// The fd is never closed twice, even if `close` is interrupted by a
+    /// signal, so a descriptor number reused by another thread is safe.
+    pub fn close(&mut self) {
+        if self.fd >= 0 {
+            safe::safe_close(self.fd);
+            self.fd = -1;
+        }
     }
+}
 
-    pub fn nonblock(&mut self) {
-        self.setblocking(false);
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
     }
+}
 
-    pub fn close(&mut self) {
-        safe::safe_close(self.fd);
+impl FromRawFd for Socket {
+    /// Take ownership of an open socket descriptor. The address family is
+    /// asked from the kernel, and is `Unknown` if that fails.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor that nothing else will close.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let mut sock = Self::from_fd(fd, AddressFamily::Unknown(-1));
+        if let Ok(af) = sock.domain() {
+            sock.af = af;
+        }
+        sock
+    }
+}
+
+impl IntoRawFd for Socket {
+    /// Give up ownership of the descriptor without closing it.
+    fn into_raw_fd(mut self) -> RawFd {
+        std::mem::replace(&mut self.fd, -1)
     }
 }
 
@@ -217,16 +3808,27 @@ impl Drop for Socket {
     }
 }
 
+/// Milliseconds left until `deadline`, rounded up so a poll never returns
+/// just before it.
+fn poll_timeout(deadline: Instant) -> i32 {
+    let left = deadline.saturating_duration_since(Instant::now());
+    left.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+}
+
 /// Convert an Internet version 4 address from a string
 /// into a u32 address.
 ///
 /// * Returns a `Result<u32, usize>`. If the result is Err, it will return
-/// the first index dotted quad to fail. The first dotted quad
-/// is 0, the second is 1, and so forth. If the result is Ok,
-/// the wrapped value will be a u32 of the IP address.
+///   the first index dotted quad to fail. The first dotted quad
+///   is 0, the second is 1, and so forth. If the result is Ok,
+///   the wrapped value will be a u32 of the IP address.
 ///
 /// * Unspecified values of an incomplete IP address are set to 0.
 ///
+/// * Every dotted quad that is present must be a decimal number from 0 to
+///   255 with no sign. An empty one, as from a leading, trailing or
+///   doubled dot, is an error at its index, and so is a fifth one.
+///
 /// * Returns in host byte order.
 ///
 /// # Examples
@@ -251,24 +3853,343 @@ impl Drop for Socket {
 /// let addr = mzsocket::inet_addr("127.512.711.299");
 /// // prints Error @ 1
 /// println!("Error @ {}", addr.unwrap_err());
+///
+/// // Empty dotted quads
+/// assert_eq!(mzsocket::inet_addr(""), Err(0));
+/// assert_eq!(mzsocket::inet_addr(".1.2.3"), Err(0));
+/// assert_eq!(mzsocket::inet_addr("1..2.3"), Err(1));
+/// assert_eq!(mzsocket::inet_addr("1.2."), Err(2));
+/// assert_eq!(mzsocket::inet_addr("1.2.3.4."), Err(4));
+///
+/// // Too many dotted quads, or a sign
+/// assert_eq!(mzsocket::inet_addr("1.2.3.4.5"), Err(4));
+/// assert_eq!(mzsocket::inet_addr("1.+2.3.4"), Err(1));
+/// ```
 pub fn inet_addr(addr: &str) -> Result<u32, usize> {
     let mut ret = 0;
-    let mut addrs = addr.split('.');
-    for i in 0..4 {
-        if let Some(k) = addrs.next() {
-            let parsed_result = k.parse::<u32>();
-            if parsed_result.is_err() {
-                return Err(i);
-            }
-            let parsed_value = parsed_result.unwrap();
-            if parsed_value > 255 {
-                return Err(i);
-            }
-            let bitp = 8 * (3 - i);
-            ret |= parsed_value << bitp;
-        } else {
-            return Ok(ret);
+    for (i, k) in addr.split('.').enumerate() {
+        if i > 3 || k.is_empty() || !k.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(i);
+        }
+        let parsed_value = k.parse::<u32>().map_err(|_| i)?;
+        if parsed_value > 255 {
+            return Err(i);
+        }
+        let bitp = 8 * (3 - i);
+        ret |= parsed_value << bitp;
+    }
+    Ok(ret)
+}
+
+/// Convert an Internet version 6 address from a string
+/// into a u128 address.
+///
+/// * Accepts colon-separated groups of up to four hex digits, a single
+///   `::` standing in for a run of zero groups, and a dotted-quad IPv4
+///   address in place of the last two groups (`::ffff:192.0.2.1`).
+///
+/// * Returns a `Result<u128, usize>`. If the result is Err, it will return
+///   the index of the first group to fail, counting from 0 in the order
+///   written. An address with too few or too many groups fails at the
+///   index of the first missing or extra group.
+///
+/// * Returns in host byte order.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(mzsocket::inet6_addr("::1"), Ok(1));
+/// assert_eq!(
+///     mzsocket::inet6_addr("2001:db8::8:800:200c:417a"),
+///     Ok(0x2001_0db8_0000_0000_0008_0800_200c_417a)
+/// );
+/// assert_eq!(mzsocket::inet6_addr("::ffff:127.0.0.1"), Ok(0xffff_7f00_0001));
+///
+/// // Not a hex group
+/// assert_eq!(mzsocket::inet6_addr("fe80::1:wxyz"), Err(2));
+/// // Only seven groups and no "::"
+/// assert_eq!(mzsocket::inet6_addr("1:2:3:4:5:6:7"), Err(7));
+/// ```
+pub fn inet6_addr(addr: &str) -> Result<u128, usize> {
+    let mut head = Vec::with_capacity(8);
+    let mut tail = Vec::with_capacity(8);
+    match addr.find("::") {
+        Some(i) => {
+            inet6_groups(&addr[..i], &mut head, 0, false)?;
+            inet6_groups(&addr[i + 2..], &mut tail, head.len(), true)?;
+            if head.len() + tail.len() > 7 {
+                return Err(7);
+            }
+        }
+        None => {
+            inet6_groups(addr, &mut head, 0, true)?;
+            if head.len() != 8 {
+                return Err(head.len());
+            }
         }
     }
+    let mut ret = 0u128;
+    for (i, g) in head.iter().enumerate() {
+        ret |= (*g as u128) << (16 * (7 - i));
+    }
+    for (i, g) in tail.iter().rev().enumerate() {
+        ret |= (*g as u128) << (16 * i);
+    }
     Ok(ret)
 }
+
+/// Parse the colon-separated groups of one side of a `::` into `out`.
+/// `first` is the index of the first group, used for error reporting, and
+/// `last` says whether this part ends the address and so may end with a
+/// dotted quad.
+fn inet6_groups(part: &str, out: &mut Vec<u16>, first: usize, last: bool) -> Result<(), usize> {
+    if part.is_empty() {
+        return Ok(());
+    }
+    let count = part.split(':').count();
+    for (j, g) in part.split(':').enumerate() {
+        let idx = first + out.len();
+        if last && j == count - 1 && g.contains('.') {
+            if g.split('.').count() != 4 {
+                return Err(idx);
+            }
+            let v4 = inet_addr(g).map_err(|_| idx)?;
+            out.push((v4 >> 16) as u16);
+            out.push(v4 as u16);
+        } else {
+            if g.is_empty() || g.len() > 4 || !g.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(idx);
+            }
+            out.push(u16::from_str_radix(g, 16).map_err(|_| idx)?);
+        }
+        if first + out.len() > 8 {
+            return Err(8);
+        }
+    }
+    Ok(())
+}
+
+/// Convert an IPv6 address with an optional zone suffix, such as
+/// `"fe80::1%eth0"` or `"fe80::1%2"`, into a u128 address and a scope id,
+/// ready for `BindFamily::Inet6Scoped`.
+///
+/// * The zone may be an interface index or an interface name, which is
+///   looked up with `if_nametoindex`. Without a zone the scope id is 0.
+///
+/// * Errors are reported as for `inet6_addr`. A zone that is neither a
+///   number nor a known interface fails at index 8, just past the last group.
+///
+/// # Examples
+///
+/// ```
+/// let lo = mzsocket::if_nametoindex("lo").unwrap();
+/// assert_eq!(
+///     mzsocket::inet6_addr_scoped("fe80::1%lo"),
+///     Ok((0xfe80_0000_0000_0000_0000_0000_0000_0001, lo))
+/// );
+/// assert_eq!(mzsocket::inet6_addr_scoped("fe80::1%7"), Ok((0xfe80 << 112 | 1, 7)));
+/// assert_eq!(mzsocket::inet6_addr_scoped("fe80::1%nosuchif0"), Err(8));
+/// ```
+pub fn inet6_addr_scoped(addr: &str) -> Result<(u128, u32), usize> {
+    let (addr, zone) = match addr.split_once('%') {
+        Some((a, z)) => (a, Some(z)),
+        None => (addr, None),
+    };
+    let ip = inet6_addr(addr)?;
+    let scope = match zone {
+        None => 0,
+        Some(z) => match z.parse::<u32>() {
+            Ok(idx) => idx,
+            Err(_) => if_nametoindex(z).map_err(|_| 8usize)?,
+        },
+    };
+    Ok((ip, scope))
+}
+
+/// Look up the index of the network interface called `name`.
+pub fn if_nametoindex(name: &str) -> Result<u32, SocketError> {
+    let cname = CString::new(name).map_err(|_| SocketError::Other(error::EINVAL))?;
+    match safe::safe_if_nametoindex(&cname) {
+        0 => Err(SocketError::last()),
+        idx => Ok(idx),
+    }
+}
+
+/// The MTU of the network interface called `name` (`SIOCGIFMTU`): the
+/// largest packet it sends or receives in one link-layer frame. Unknown
+/// interfaces fail with `ENODEV` as `SocketError::Other`. Linux only.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{interface_addrs, interface_mtu_by_name, IfFlags};
+///
+/// let addrs = interface_addrs().unwrap();
+/// let lo = addrs.iter().find(|a| a.flags.contains(IfFlags::LOOPBACK)).unwrap();
+/// assert!(interface_mtu_by_name(&lo.name).unwrap() >= 1280);
+/// assert!(interface_mtu_by_name("no-such-if0").is_err());
+/// ```
+#[cfg(target_os = "linux")]
+pub fn interface_mtu_by_name(name: &str) -> Result<u32, SocketError> {
+    let cname = CString::new(name).map_err(|_| SocketError::Other(error::EINVAL))?;
+    if name.len() >= 16 {
+        return Err(SocketError::Other(error::EINVAL));
+    }
+    let sock = Socket::create(AddressFamily::Inet, SocketType::DataGram, IpProto::Ip)?;
+    match safe::safe_ioctl_ifmtu(sock.fd, consts::SIOCGIFMTU, &cname) {
+        (r, _) if r < 0 => Err(SocketError::last()),
+        (_, mtu) => Ok(mtu as u32),
+    }
+}
+
+/// Check whether something accepts TCP connections at `addr`, waiting at
+/// most `timeout`.
+///
+/// This is a plain non-blocking connect, not a raw SYN probe: when the
+/// port is open the handshake completes and the connection is closed
+/// straight away. A refused connection gives `Ok(false)`. No answer in
+/// time, as from a firewall that drops packets, gives
+/// `SocketError::TimedOut`, and other failures such as an unreachable
+/// network are returned as errors too.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{inet_addr, AddressFamily, BindFamily, Socket, SocketType};
+/// use std::time::Duration;
+///
+/// let lo = inet_addr("127.0.0.1").unwrap();
+/// let listener = Socket::listen_inet(lo, 0, None).unwrap();
+/// let open = BindFamily::Inet(lo, listener.local_port().unwrap());
+/// assert_eq!(mzsocket::port_open(open, Duration::from_secs(1)), Ok(true));
+///
+/// // A bound but not listening socket holds a port nothing accepts on.
+/// let mut idle = Socket::new(AddressFamily::Inet, SocketType::Stream, None).unwrap();
+/// idle.bind(BindFamily::Inet(lo, 0)).unwrap();
+/// let closed = BindFamily::Inet(lo, idle.local_port().unwrap());
+/// assert_eq!(mzsocket::port_open(closed, Duration::from_secs(1)), Ok(false));
+/// ```
+pub fn port_open(addr: BindFamily, timeout: Duration) -> Result<bool, SocketError> {
+    let mut sock = Socket::create(addr.family(), SocketType::Stream, IpProto::Ip)?;
+    match sock.connect_timeout(addr, timeout) {
+        Ok(()) => Ok(true),
+        Err(SocketError::ConnectionRefused) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// List the IPv4 and IPv6 addresses of every local network interface,
+/// one entry per address (`getifaddrs`).
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{BindFamily, IfFlags};
+///
+/// let addrs = mzsocket::interface_addrs().unwrap();
+/// let lo = addrs
+///     .iter()
+///     .find(|a| a.addr == BindFamily::Inet(0x7f00_0001, 0))
+///     .unwrap();
+/// assert!(lo.flags.contains(IfFlags::UP | IfFlags::LOOPBACK));
+///
+/// // Pick an address to advertise.
+/// if let Some(a) = addrs.iter().find(|a| !a.flags.contains(IfFlags::LOOPBACK)) {
+///     println!("advertising {:?} on {}", a.addr, a.name);
+/// }
+/// ```
+pub fn interface_addrs() -> Result<Vec<InterfaceAddr>, SocketError> {
+    safe::safe_getifaddrs().map_err(|_| SocketError::last())
+}
+
+/// Resolve `host` into every IPv4 and IPv6 address it has, each paired with
+/// `port` and ready to hand to `connect` or `bind`.
+///
+/// `host` may be a name or a numeric address. Resolution uses the system
+/// resolver (`getaddrinfo`), so `/etc/hosts`, DNS and friends all apply.
+/// A failed lookup is reported as `SocketError::Resolve` with the resolver's
+/// error code.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::BindFamily;
+///
+/// let addrs = mzsocket::resolve("localhost", 8080).unwrap();
+/// assert!(addrs.iter().any(|a| matches!(a, BindFamily::Inet(0x7f00_0001, 8080))));
+/// assert!(mzsocket::resolve("no-such-host.invalid", 80).is_err());
+/// ```
+pub fn resolve(host: &str, port: u16) -> Result<Vec<BindFamily>, SocketError> {
+    let chost = CString::new(host).map_err(|_| SocketError::Other(error::EINVAL))?;
+    match safe::safe_getaddrinfo(&chost, port) {
+        Ok(addrs) => Ok(addrs),
+        Err(error::EAI_SYSTEM) => Err(SocketError::last()),
+        Err(code) => Err(SocketError::Resolve(code)),
+    }
+}
+
+/// The address family of a raw sockaddr in `bytes`, read from its leading
+/// `sa_family` field (a `u16` in host byte order). Returns `None` if
+/// `bytes` is shorter than that or the family isn't one `AddressFamily`
+/// names.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{sockaddr_family, AddressFamily};
+///
+/// let sa = |family: u16| {
+///     let mut bytes = [0u8; 16];
+///     bytes[..2].copy_from_slice(&family.to_ne_bytes());
+///     bytes
+/// };
+/// assert_eq!(sockaddr_family(&sa(0)), Some(AddressFamily::Unspec));
+/// assert_eq!(sockaddr_family(&sa(1)), Some(AddressFamily::Unix));
+/// assert_eq!(sockaddr_family(&sa(2)), Some(AddressFamily::Inet));
+/// assert_eq!(sockaddr_family(&sa(10)), Some(AddressFamily::Inet6));
+/// assert_eq!(sockaddr_family(&sa(17)), Some(AddressFamily::Packet));
+/// assert_eq!(sockaddr_family(&sa(38)), None);
+/// assert_eq!(sockaddr_family(&sa(2)[..1]), None);
+/// assert_eq!(sockaddr_family(&[]), None);
+/// ```
+pub fn sockaddr_family(bytes: &[u8]) -> Option<AddressFamily> {
+    match bytes {
+        &[a, b, ..] => match AddressFamily::from_raw(u16::from_ne_bytes([a, b]) as i32) {
+            AddressFamily::Unknown(_) => None,
+            af => Some(af),
+        },
+        _ => None,
+    }
+}
+
+/// Ignore `SIGPIPE` for the whole process, so writing to a connection the
+/// peer has closed fails with `EPIPE` instead of killing the program.
+///
+/// `MsgFlags::NOSIGNAL` does this per call, but plain `write` and other
+/// paths don't take flags. Server programs usually call this once at
+/// startup. Calling it again is harmless; it then returns the `SIG_IGN`
+/// disposition it installed the first time. The returned handler can be
+/// given to `restore_sigpipe` to put things back.
+///
+/// # Examples
+///
+/// ```
+/// let prev = mzsocket::ignore_sigpipe().unwrap();
+/// assert!(mzsocket::ignore_sigpipe().unwrap().is_ignored());
+/// mzsocket::restore_sigpipe(prev).unwrap();
+/// ```
+pub fn ignore_sigpipe() -> Result<SigHandler, SocketError> {
+    set_sigpipe(consts::SIG_IGN)
+}
+
+/// Reinstate a `SIGPIPE` disposition returned by `ignore_sigpipe`.
+pub fn restore_sigpipe(prev: SigHandler) -> Result<(), SocketError> {
+    set_sigpipe(prev.0).map(|_| ())
+}
+
+fn set_sigpipe(handler: usize) -> Result<SigHandler, SocketError> {
+    match safe::safe_signal(consts::SIGPIPE, handler) {
+        consts::SIG_ERR => Err(SocketError::last()),
+        prev => Ok(structs::SigHandler(prev)),
+    }
+}
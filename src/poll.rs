@@ -0,0 +1,128 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{safe, SocketError};
+use std::marker::PhantomData;
+use std::ops::{BitOr, BitOrAssign};
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+/// Events to wait for, and that occurred, in a `poll`. Combine them
+/// with `|`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PollEvents(pub i16);
+
+impl PollEvents {
+    pub const NONE: Self = Self(0);
+    /// Data is ready to read, or a listening socket has a connection.
+    pub const IN: Self = Self(0x1);
+    /// Urgent (out-of-band) data is ready to read.
+    pub const PRI: Self = Self(0x2);
+    /// Writing won't block.
+    pub const OUT: Self = Self(0x4);
+    /// An error is pending. Always reported, never needs asking for.
+    pub const ERR: Self = Self(0x8);
+    /// The peer hung up. Always reported, never needs asking for.
+    pub const HUP: Self = Self(0x10);
+    /// The fd isn't open.
+    pub const NVAL: Self = Self(0x20);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PollEvents {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PollEvents {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// One entry of a `poll` set (`struct pollfd`).
+///
+/// A `PollFd` borrows the socket it watches, so the socket can't be
+/// closed or dropped while it is still in a poll set. Without that, a
+/// poll could end up watching an fd number the kernel has since handed to
+/// something else.
+#[repr(C)]
+pub struct PollFd<'a> {
+    fd: i32,
+    events: i16,
+    revents: i16,
+    _sock: PhantomData<&'a ()>,
+}
+
+impl<'a> PollFd<'a> {
+    /// Watch `sock`, which may be a `Socket`, a split half or anything else
+    /// with an fd, for `events`.
+    pub fn new<S: AsRawFd + ?Sized>(sock: &'a S, events: PollEvents) -> Self {
+        Self {
+            fd: sock.as_raw_fd(),
+            events: events.0,
+            revents: 0,
+            _sock: PhantomData,
+        }
+    }
+
+    /// The events that occurred in the last `poll`.
+    pub fn revents(&self) -> PollEvents {
+        PollEvents(self.revents)
+    }
+}
+
+/// Wait until at least one entry in `fds` is ready, or `timeout` passes
+/// (`None` waits for ever). Returns how many entries have events, 0 on
+/// timeout. Check each entry's `revents()` to see which.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{poll, PollEvents, PollFd, Socket, SocketType};
+/// use std::time::Duration;
+///
+/// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+/// let mut fds = [PollFd::new(&server, PollEvents::IN)];
+/// assert_eq!(poll(&mut fds, Some(Duration::ZERO)).unwrap(), 0);
+///
+/// client.write(b"ping").unwrap();
+/// assert_eq!(poll(&mut fds, Some(Duration::from_secs(1))).unwrap(), 1);
+/// assert!(fds[0].revents().contains(PollEvents::IN));
+/// ```
+pub fn poll(fds: &mut [PollFd], timeout: Option<Duration>) -> Result<usize, SocketError> {
+    let ms = match timeout {
+        None => -1,
+        Some(t) => t.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+    };
+    match safe::safe_poll(fds, ms) {
+        r if r < 0 => Err(SocketError::last()),
+        r => Ok(r as usize),
+    }
+}
@@ -0,0 +1,64 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{RawFd, SocketError};
+use std::ops::BitOr;
+
+/// What a reactor should watch a socket for. Combine with `|`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interest(pub u8);
+
+impl Interest {
+    pub const READABLE: Self = Self(0x1);
+    pub const WRITABLE: Self = Self(0x2);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The hook between a `Socket` and an event loop, so the crate works with
+/// any async runtime without depending on one.
+///
+/// A runtime adapter implements this over its own epoll, kqueue or other
+/// poller. `Socket::register_with` switches the socket to non-blocking
+/// mode and hands its fd and `Socket::readiness_interest` to `register`.
+/// From then on, calls that return `SocketError::WouldBlock` should be
+/// retried once the reactor reports the fd ready. The socket must stay
+/// alive, and be deregistered before it is dropped.
+pub trait Reactor {
+    /// Whatever the reactor uses to identify a registration.
+    type Token;
+
+    fn register(&mut self, fd: RawFd, interest: Interest) -> Result<Self::Token, SocketError>;
+
+    fn deregister(&mut self, fd: RawFd) -> Result<(), SocketError>;
+}
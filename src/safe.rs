@@ -22,183 +22,615 @@
 //! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 //! THE SOFTWARE.
 
-use super::structs::{InetAddr, Inet6Addr, UNIX_PATH_LEN};
+use super::structs::{IfFlags, InetAddr, Inet6Addr, InterfaceAddr, IPV6_FLOWLABEL_MASK, UNIX_PATH_LEN};
 use super::{
-    AddressFamily, BindFamily, Inet6SockAddr, InetSockAddr, IpProto, SocketType, UnixSockAddr, 
+    AddressFamily, BindFamily, Inet6SockAddr, InetSockAddr, IpProto, PollFd, Shutdown, SocketType,
+    UnixSockAddr,
 };
-use std::ffi::{c_int, c_uint, c_ushort, c_void};
+use std::ffi::{c_char, c_int, c_short, c_uint, c_ulong, c_ushort, c_void, CStr};
 use std::mem::size_of;
 
 extern "C" {
     fn socket(af: c_int, socktype: c_int, proto: c_int) -> c_int;
     fn bind(fd: c_int, s: *const c_void, slen: c_uint) -> c_int;
     fn connect(fd: c_int, s: *const c_void, slen: c_uint) -> c_int;
+    fn sendto(
+        fd: c_int,
+        buffer: *const u8,
+        buflen: c_ulong,
+        flags: c_int,
+        s: *const c_void,
+        slen: c_uint,
+    ) -> i64;
+    fn getsockopt(fd: c_int, level: c_int, name: c_int, val: *mut c_void, len: *mut c_uint) -> c_int;
+    fn setsockopt(fd: c_int, level: c_int, name: c_int, val: *const c_void, len: c_uint) -> c_int;
     fn htons(val: c_ushort) -> c_ushort;
     fn htonl(val: c_uint) -> c_uint;
-    fn close(fd: c_int);
+    fn shutdown(fd: c_int, how: c_int) -> c_int;
+    fn socketpair(af: c_int, socktype: c_int, proto: c_int, fds: *mut c_int) -> c_int;
+    fn dup(fd: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+    fn if_nametoindex(name: *const c_char) -> c_uint;
+    fn getaddrinfo(
+        node: *const c_char,
+        service: *const c_char,
+        hints: *const AddrInfo,
+        res: *mut *mut AddrInfo,
+    ) -> c_int;
+    fn freeaddrinfo(res: *mut AddrInfo);
+    fn gai_strerror(code: c_int) -> *const c_char;
+    fn ntohl(val: c_uint) -> c_uint;
+    fn poll(fds: *mut PollFdRaw, nfds: c_ulong, timeout: c_int) -> c_int;
+    fn recvmsg(fd: c_int, msg: *mut MsgHdr, flags: c_int) -> i64;
+    fn sendmsg(fd: c_int, msg: *const MsgHdr, flags: c_int) -> i64;
+    fn writev(fd: c_int, iov: *const IoVec, iovcnt: c_int) -> i64;
+    #[cfg(target_os = "linux")]
+    fn recvmmsg(fd: c_int, msgs: *mut MMsgHdr, vlen: c_uint, flags: c_int, timeout: *mut c_void) -> c_int;
+    #[cfg(target_os = "linux")]
+    fn sendmmsg(fd: c_int, msgs: *mut MMsgHdr, vlen: c_uint, flags: c_int) -> c_int;
+    fn signal(sig: c_int, handler: usize) -> usize;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    fn getifaddrs(ifap: *mut *mut IfAddrs) -> c_int;
+    fn freeifaddrs(ifa: *mut IfAddrs);
+}
+
+/// `struct ifaddrs`
+#[repr(C)]
+struct IfAddrs {
+    next: *mut IfAddrs,
+    name: *const c_char,
+    flags: c_uint,
+    addr: *mut c_void,
+    netmask: *mut c_void,
+    ifu: *mut c_void,
+    data: *mut c_void,
+}
+
+/// `struct iovec`
+#[repr(C)]
+struct IoVec {
+    base: *mut c_void,
+    len: c_ulong,
+}
+
+/// `struct msghdr`
+#[repr(C)]
+struct MsgHdr {
+    name: *mut c_void,
+    namelen: c_uint,
+    iov: *mut IoVec,
+    iovlen: c_ulong,
+    control: *mut c_void,
+    controllen: c_ulong,
+    flags: c_int,
+}
+
+/// `struct mmsghdr`
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct MMsgHdr {
+    hdr: MsgHdr,
+    len: c_uint,
+}
+
+/// `struct cmsghdr`, which is followed by its data.
+#[repr(C)]
+struct CmsgHdr {
+    len: c_ulong,
+    level: c_int,
+    kind: c_int,
+}
+
+/// `struct pollfd`
+#[repr(C)]
+struct PollFdRaw {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+/// `struct addrinfo`
+#[repr(C)]
+struct AddrInfo {
+    flags: c_int,
+    family: c_int,
+    socktype: c_int,
+    protocol: c_int,
+    addrlen: c_uint,
+    addr: *mut c_void,
+    canonname: *mut c_char,
+    next: *mut AddrInfo,
 }
 
 pub(super) fn safe_socket(af: AddressFamily, st: SocketType, pt: IpProto) -> i32 {
-    unsafe { socket(af as c_int, st as c_int, pt as c_int) as i32 }
+    unsafe { socket(af.as_raw(), st.as_raw(), pt.as_raw()) }
+}
+
+pub(super) fn safe_socketpair(af: AddressFamily, st: SocketType, fds: &mut [c_int; 2]) -> i32 {
+    unsafe { socketpair(af.as_raw(), st.as_raw(), 0, fds.as_mut_ptr()) }
 }
 
 pub(super) fn safe_bind(fd: c_int, bf: BindFamily) -> i32 {
-    match bf {
-        BindFamily::Inet(addr, port) => bind_inet(fd, addr, port),
-        BindFamily::Inet6(addr, port) => bind_inet6(fd, addr, port),
-        BindFamily::Unix(path) => bind_unix(fd, path),
-    }
+    with_sockaddr(&bf, |sa, slen| unsafe { bind(fd, sa, slen) })
 }
 
-pub(super) fn bind_inet(fd: c_int, ipaddr: u32, port: u16) -> i32 {
-    unsafe {
-        let isa = InetSockAddr {
-            family: AddressFamily::Inet as u16,
-            port: htons(port),
-            addr: InetAddr::new(htonl(ipaddr)),
-            reserved: 0,
-        };
-        bind(
-            fd,
-            &isa as *const InetSockAddr as *const c_void,
-            size_of::<InetSockAddr>() as c_uint,
-        ) as i32
+pub(super) fn safe_connect(fd: c_int, bf: BindFamily) -> i32 {
+    with_sockaddr(&bf, |sa, slen| unsafe { connect(fd, sa, slen) })
+}
+
+pub(super) fn safe_sendto(fd: c_int, buffer: &[u8], flags: c_int, bf: &BindFamily) -> i64 {
+    with_sockaddr(bf, |sa, slen| unsafe {
+        sendto(fd, buffer.as_ptr(), buffer.len() as c_ulong, flags, sa, slen)
+    })
+}
+
+/// Build the C socket address for `bf` and hand it to `f` along with its
+/// length.
+fn with_sockaddr<R>(bf: &BindFamily, f: impl FnOnce(*const c_void, c_uint) -> R) -> R {
+    match *bf {
+        BindFamily::Inet(addr, port) => {
+            let isa = InetSockAddr {
+                family: AddressFamily::Inet.as_raw() as u16,
+                port: unsafe { htons(port) },
+                addr: InetAddr::new(unsafe { htonl(addr) }),
+                reserved: 0,
+            };
+            f(
+                &isa as *const InetSockAddr as *const c_void,
+                size_of::<InetSockAddr>() as c_uint,
+            )
+        }
+        BindFamily::Inet6(addr, port) => {
+            let isa = inet6_sockaddr(addr, port, 0, 0);
+            f(
+                &isa as *const Inet6SockAddr as *const c_void,
+                size_of::<Inet6SockAddr>() as c_uint,
+            )
+        }
+        BindFamily::Inet6Scoped(addr, port, scopeid) => {
+            let isa = inet6_sockaddr(addr, port, scopeid, 0);
+            f(
+                &isa as *const Inet6SockAddr as *const c_void,
+                size_of::<Inet6SockAddr>() as c_uint,
+            )
+        }
+        BindFamily::Inet6Flow(addr, port, scopeid, label) => {
+            let isa = inet6_sockaddr(addr, port, scopeid, label);
+            f(
+                &isa as *const Inet6SockAddr as *const c_void,
+                size_of::<Inet6SockAddr>() as c_uint,
+            )
+        }
+        BindFamily::Unix(ref path) => {
+            let usa = unix_sockaddr(path);
+            f(
+                &usa as *const UnixSockAddr as *const c_void,
+                size_of::<UnixSockAddr>() as c_uint,
+            )
+        }
     }
 }
 
-pub(super) fn bind_inet6(fd: c_int, ipaddr: u128, port: u16) -> i32 {
-    let saddr = [
-        ((ipaddr >> 120) & 0xFF) as u8,
-        ((ipaddr >> 112) & 0xFF) as u8,
-        ((ipaddr >> 104) & 0xFF) as u8,
-        ((ipaddr >> 96) & 0xFF) as u8,
-        ((ipaddr >> 88) & 0xFF) as u8,
-        ((ipaddr >> 80) & 0xFF) as u8,
-        ((ipaddr >> 72) & 0xFF) as u8,
-        ((ipaddr >> 64) & 0xFF) as u8,
-        ((ipaddr >> 56) & 0xFF) as u8,
-        ((ipaddr >> 48) & 0xFF) as u8,
-        ((ipaddr >> 40) & 0xFF) as u8,
-        ((ipaddr >> 32) & 0xFF) as u8,
-        ((ipaddr >> 24) & 0xFF) as u8,
-        ((ipaddr >> 16) & 0xFF) as u8,
-        ((ipaddr >> 8) & 0xFF) as u8,
-        (ipaddr & 0xFF) as u8,
-    ];
-    unsafe {
-        let isa = Inet6SockAddr {
-            family: AddressFamily::Inet6 as u16,
-            port: htons(port),
-            flowinfo: 0,
-            addr: Inet6Addr::new_8(saddr),
-            scopeid: 0,
-        };
-        bind(
-            fd,
-            &isa as *const Inet6SockAddr as *const c_void,
-            size_of::<Inet6SockAddr>() as c_uint,
-        ) as i32
+fn inet6_sockaddr(ipaddr: u128, port: u16, scopeid: u32, label: u32) -> Inet6SockAddr {
+    Inet6SockAddr {
+        family: AddressFamily::Inet6.as_raw() as u16,
+        port: unsafe { htons(port) },
+        flowinfo: unsafe { htonl(label & IPV6_FLOWLABEL_MASK) },
+        addr: Inet6Addr::new_8(ipaddr.to_be_bytes()),
+        scopeid,
     }
 }
 
-pub(super) fn bind_unix(fd: c_int, path: String) -> i32 {
-    let size = if path.len() < (UNIX_PATH_LEN - 1) {
-        path.len()
-    } else {
-        UNIX_PATH_LEN - 1
-    };
-    let mut stpath = [0u8; 108];
-    let mut bytes = path.bytes();
-    for i in stpath.iter_mut().take(size) {
-        *i = bytes.next().unwrap();
+/// Paths longer than the sockaddr can hold are truncated, always leaving
+/// room for the terminating NUL.
+fn unix_sockaddr(path: &str) -> UnixSockAddr {
+    let size = path.len().min(UNIX_PATH_LEN - 1);
+    let mut stpath = [0u8; UNIX_PATH_LEN];
+    stpath[..size].copy_from_slice(&path.as_bytes()[..size]);
+    UnixSockAddr {
+        family: AddressFamily::Unix.as_raw() as u16,
+        path: stpath,
     }
-    stpath[size] = 0;
+}
+
+pub(super) fn safe_getsockopt<T>(fd: c_int, level: c_int, name: c_int, val: &mut T) -> i32 {
+    let mut len = size_of::<T>() as c_uint;
+    unsafe { getsockopt(fd, level, name, val as *mut T as *mut c_void, &mut len) }
+}
+
+/// getsockopt into a byte buffer, for options whose size varies. Returns
+/// the result and how many bytes the kernel wrote.
+#[cfg(target_os = "linux")]
+pub(super) fn safe_getsockopt_buf(fd: c_int, level: c_int, name: c_int, buf: &mut [u8]) -> (i32, usize) {
+    let mut len = buf.len() as c_uint;
+    let r = unsafe { getsockopt(fd, level, name, buf.as_mut_ptr() as *mut c_void, &mut len) };
+    (r, (len as usize).min(buf.len()))
+}
 
+/// setsockopt from a byte buffer, for options whose size varies.
+#[cfg(target_os = "linux")]
+pub(super) fn safe_setsockopt_buf(fd: c_int, level: c_int, name: c_int, buf: &[u8]) -> i32 {
+    unsafe { setsockopt(fd, level, name, buf.as_ptr() as *const c_void, buf.len() as c_uint) }
+}
+
+pub(super) fn safe_setsockopt<T>(fd: c_int, level: c_int, name: c_int, val: &T) -> i32 {
     unsafe {
-        let usa = UnixSockAddr {
-            family: AddressFamily::Unix as u16,
-            path: stpath,
-        };
-        bind(
+        setsockopt(
             fd,
-            &usa as *const UnixSockAddr as *const c_void,
-            size_of::<UnixSockAddr>() as c_uint,
-        ) as i32
+            level,
+            name,
+            val as *const T as *const c_void,
+            size_of::<T>() as c_uint,
+        )
     }
 }
 
-pub(super) fn safe_connect(fd: c_int, bf: BindFamily) -> i32 {
-    unsafe {
-        match bf {
-            BindFamily::Inet(addr, port) => {
-                let s = InetSockAddr {
-                    family: AddressFamily::Inet as u16,
-                    port: htons(port),
-                    addr: InetAddr::new(htonl(addr)),
-                    reserved: 0,
-                };
-                connect(
-                    fd as i32,
-                    &s as *const InetSockAddr as *const c_void,
-                    size_of::<InetSockAddr>() as c_uint,
-                ) as i32
+pub(super) fn safe_shutdown(fd: c_int, how: Shutdown) -> i32 {
+    unsafe { shutdown(fd, how.as_raw()) }
+}
+
+pub(super) fn safe_dup(fd: c_int) -> i32 {
+    unsafe { dup(fd) }
+}
+
+pub(super) fn safe_if_nametoindex(name: &CStr) -> u32 {
+    unsafe { if_nametoindex(name.as_ptr()) }
+}
+
+/// Resolve `host` with getaddrinfo into one `BindFamily` per IPv4 or IPv6
+/// address. Errors are the raw `EAI_*` code.
+pub(super) fn safe_getaddrinfo(host: &CStr, port: u16) -> Result<Vec<BindFamily>, i32> {
+    // Ask for stream sockets only. Otherwise every address comes back once
+    // per socket type, and a BindFamily doesn't carry the type anyway.
+    let hints = AddrInfo {
+        flags: 0,
+        family: AddressFamily::Unspec.as_raw(),
+        socktype: SocketType::Stream.as_raw(),
+        protocol: 0,
+        addrlen: 0,
+        addr: std::ptr::null_mut(),
+        canonname: std::ptr::null_mut(),
+        next: std::ptr::null_mut(),
+    };
+    let mut res: *mut AddrInfo = std::ptr::null_mut();
+    let r = unsafe { getaddrinfo(host.as_ptr(), std::ptr::null(), &hints, &mut res) };
+    if r != 0 {
+        return Err(r);
+    }
+    let mut ret = Vec::new();
+    let mut cur = res;
+    while !cur.is_null() {
+        unsafe {
+            let ai = &*cur;
+            match AddressFamily::from_raw(ai.family) {
+                AddressFamily::Inet if ai.addrlen as usize >= size_of::<InetSockAddr>() => {
+                    let sa = &*(ai.addr as *const InetSockAddr);
+                    ret.push(BindFamily::Inet(ntohl(sa.addr.addr32), port));
+                }
+                AddressFamily::Inet6 if ai.addrlen as usize >= size_of::<Inet6SockAddr>() => {
+                    let sa = &*(ai.addr as *const Inet6SockAddr);
+                    let ip = u128::from_be_bytes(sa.addr.addr8);
+                    ret.push(match sa.scopeid {
+                        0 => BindFamily::Inet6(ip, port),
+                        scope => BindFamily::Inet6Scoped(ip, port, scope),
+                    });
+                }
+                _ => {}
             }
-            BindFamily::Inet6(ipaddr, port) => {
-                let saddr = [
-                    ((ipaddr >> 120) & 0xFF) as u8,
-                    ((ipaddr >> 112) & 0xFF) as u8,
-                    ((ipaddr >> 104) & 0xFF) as u8,
-                    ((ipaddr >> 96) & 0xFF) as u8,
-                    ((ipaddr >> 88) & 0xFF) as u8,
-                    ((ipaddr >> 80) & 0xFF) as u8,
-                    ((ipaddr >> 72) & 0xFF) as u8,
-                    ((ipaddr >> 64) & 0xFF) as u8,
-                    ((ipaddr >> 56) & 0xFF) as u8,
-                    ((ipaddr >> 48) & 0xFF) as u8,
-                    ((ipaddr >> 40) & 0xFF) as u8,
-                    ((ipaddr >> 32) & 0xFF) as u8,
-                    ((ipaddr >> 24) & 0xFF) as u8,
-                    ((ipaddr >> 16) & 0xFF) as u8,
-                    ((ipaddr >> 8) & 0xFF) as u8,
-                    (ipaddr & 0xFF) as u8,
-                ];
-                let s = Inet6SockAddr {
-                    family: AddressFamily::Inet6 as u16,
-                    port: htons(port),
-                    flowinfo: 0,
-                    addr: Inet6Addr::new_8(saddr),
-                    scopeid: 0,
-                };
-                connect(
-                    fd as i32,
-                    &s as *const Inet6SockAddr as *const c_void,
-                    size_of::<Inet6SockAddr>() as c_uint,
-                ) as i32
+            cur = ai.next;
+        }
+    }
+    unsafe { freeaddrinfo(res) };
+    Ok(ret)
+}
+
+/// Every IPv4 and IPv6 address of the local interfaces, or the failing
+/// call's result.
+pub(super) fn safe_getifaddrs() -> Result<Vec<InterfaceAddr>, i32> {
+    let mut res: *mut IfAddrs = std::ptr::null_mut();
+    let r = unsafe { getifaddrs(&mut res) };
+    if r < 0 {
+        return Err(r);
+    }
+    let mut ret = Vec::new();
+    let mut cur = res;
+    while !cur.is_null() {
+        unsafe {
+            let ifa = &*cur;
+            cur = ifa.next;
+            if ifa.addr.is_null() {
+                continue;
             }
-            BindFamily::Unix(path) => {
-                let size = if path.len() <= 107 { path.len() } else { 107 };
-                let mut stpath = [0u8; 108];
-                let mut bytes = path.bytes();
-                for i in stpath.iter_mut().take(size) {
-                    *i = bytes.next().unwrap();
+            let family = AddressFamily::from_raw(*(ifa.addr as *const u16) as c_int);
+            let addr = match family {
+                AddressFamily::Inet => {
+                    let sa = &*(ifa.addr as *const InetSockAddr);
+                    BindFamily::Inet(ntohl(sa.addr.addr32), 0)
                 }
-                stpath[size] = 0;
-                let s = UnixSockAddr {
-                    family: AddressFamily::Unix as u16,
-                    path: stpath,
-                };
-                connect(
-                    fd as i32,
-                    &s as *const UnixSockAddr as *const c_void,
-                    size_of::<UnixSockAddr>() as c_uint,
-                ) as i32
-            }
+                AddressFamily::Inet6 => {
+                    let sa = &*(ifa.addr as *const Inet6SockAddr);
+                    let ip = u128::from_be_bytes(sa.addr.addr8);
+                    match sa.scopeid {
+                        0 => BindFamily::Inet6(ip, 0),
+                        scope => BindFamily::Inet6Scoped(ip, 0, scope),
+                    }
+                }
+                _ => continue,
+            };
+            ret.push(InterfaceAddr {
+                name: CStr::from_ptr(ifa.name).to_string_lossy().into_owned(),
+                family,
+                addr,
+                flags: IfFlags(ifa.flags),
+            });
         }
     }
+    unsafe { freeifaddrs(res) };
+    Ok(ret)
 }
 
-pub(super) fn safe_close(fd: c_int) {
-    unsafe {
-        close(fd);
+pub(super) fn safe_gai_strerror(code: i32) -> String {
+    unsafe { CStr::from_ptr(gai_strerror(code)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Wait up to `timeout_ms` (-1 for ever) for `events` on `fd`. Returns the
+/// events that occurred, 0 on timeout, or -1 on error.
+pub(super) fn safe_poll_one(fd: c_int, events: i16, timeout_ms: i32) -> i32 {
+    let mut pfd = PollFdRaw {
+        fd,
+        events,
+        revents: 0,
+    };
+    match unsafe { poll(&mut pfd, 1, timeout_ms) } {
+        r if r <= 0 => r,
+        _ => pfd.revents as i32,
+    }
+}
+
+/// `poll` on every entry of `fds`. Returns how many have events, 0 on
+/// timeout, or -1 on error.
+pub(super) fn safe_poll(fds: &mut [PollFd], timeout_ms: i32) -> i32 {
+    // PollFd is a repr(C) struct pollfd plus a zero-sized marker.
+    unsafe { poll(fds.as_mut_ptr() as *mut PollFdRaw, fds.len() as c_ulong, timeout_ms) }
+}
+
+/// `recvmsg` into a single buffer, with `control` for ancillary data.
+/// Returns the byte count (or -1) and how much of `control` was filled.
+pub(super) fn safe_recvmsg(fd: c_int, buffer: &mut [u8], control: &mut [u8], flags: c_int) -> (i64, usize) {
+    safe_recvmsg_from(fd, buffer, control, flags, std::ptr::null_mut(), 0)
+}
+
+/// `safe_recvmsg` that also stores the sender's address in the `namelen`
+/// bytes at `name`.
+pub(super) fn safe_recvmsg_from(
+    fd: c_int,
+    buffer: &mut [u8],
+    control: &mut [u8],
+    flags: c_int,
+    name: *mut c_void,
+    namelen: c_uint,
+) -> (i64, usize) {
+    let mut iov = IoVec {
+        base: buffer.as_mut_ptr() as *mut c_void,
+        len: buffer.len() as c_ulong,
+    };
+    let mut msg = MsgHdr {
+        name,
+        namelen,
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_mut_ptr() as *mut c_void,
+        controllen: control.len() as c_ulong,
+        flags: 0,
+    };
+    let r = unsafe { recvmsg(fd, &mut msg, flags) };
+    (r, (msg.controllen as usize).min(control.len()))
+}
+
+/// `sendmsg` of a single buffer, with `control` as ancillary data.
+pub(super) fn safe_sendmsg(fd: c_int, buffer: &[u8], control: &[u8], flags: c_int) -> i64 {
+    let mut iov = IoVec {
+        base: buffer.as_ptr() as *mut c_void,
+        len: buffer.len() as c_ulong,
+    };
+    let msg = MsgHdr {
+        name: std::ptr::null_mut(),
+        namelen: 0,
+        iov: &mut iov,
+        iovlen: 1,
+        control: control.as_ptr() as *mut c_void,
+        controllen: control.len() as c_ulong,
+        flags: 0,
+    };
+    unsafe { sendmsg(fd, &msg, flags) }
+}
+
+fn cmsg_align(n: usize) -> usize {
+    (n + size_of::<c_ulong>() - 1) & !(size_of::<c_ulong>() - 1)
+}
+
+/// How much ancillary buffer one control message with `data_len` bytes
+/// of data takes (`CMSG_SPACE`).
+pub(super) fn cmsg_space(data_len: usize) -> usize {
+    cmsg_align(size_of::<CmsgHdr>()) + cmsg_align(data_len)
+}
+
+/// Build an ancillary buffer holding one control message, for
+/// `safe_sendmsg`.
+pub(super) fn build_cmsg(level: c_int, kind: c_int, data: &[u8]) -> Vec<u8> {
+    let hdr = cmsg_align(size_of::<CmsgHdr>());
+    let mut control = vec![0u8; cmsg_space(data.len())];
+    let cm = CmsgHdr {
+        len: (hdr + data.len()) as c_ulong,
+        level,
+        kind,
+    };
+    unsafe { std::ptr::write_unaligned(control.as_mut_ptr() as *mut CmsgHdr, cm) };
+    control[hdr..hdr + data.len()].copy_from_slice(data);
+    control
+}
+
+// Most buffers one writev call accepts
+const IOV_MAX: usize = 1024;
+
+/// `writev` of `bufs`, or of the first `IOV_MAX` of them if there are
+/// more. Returns the number of bytes written (or -1).
+pub(super) fn safe_writev(fd: c_int, bufs: &[&[u8]]) -> i64 {
+    let iovs: Vec<IoVec> = bufs
+        .iter()
+        .take(IOV_MAX)
+        .map(|b| IoVec {
+            base: b.as_ptr() as *mut c_void,
+            len: b.len() as c_ulong,
+        })
+        .collect();
+    unsafe { writev(fd, iovs.as_ptr(), iovs.len() as c_int) }
+}
+
+/// `recvmmsg` into `bufs`, with each sender in the matching `addrs` entry
+/// and each datagram's length in `lens`. Returns the number of datagrams
+/// (or -1).
+#[cfg(target_os = "linux")]
+pub(super) fn safe_recvmmsg(
+    fd: c_int,
+    bufs: &mut [&mut [u8]],
+    addrs: &mut [InetSockAddr],
+    lens: &mut [usize],
+    flags: c_int,
+) -> i32 {
+    let n = bufs.len().min(addrs.len()).min(lens.len());
+    let mut iovs: Vec<IoVec> = bufs[..n]
+        .iter_mut()
+        .map(|b| IoVec {
+            base: b.as_mut_ptr() as *mut c_void,
+            len: b.len() as c_ulong,
+        })
+        .collect();
+    let mut msgs: Vec<MMsgHdr> = iovs
+        .iter_mut()
+        .zip(addrs[..n].iter_mut())
+        .map(|(iov, addr)| MMsgHdr {
+            hdr: MsgHdr {
+                name: addr as *mut InetSockAddr as *mut c_void,
+                namelen: size_of::<InetSockAddr>() as c_uint,
+                iov,
+                iovlen: 1,
+                control: std::ptr::null_mut(),
+                controllen: 0,
+                flags: 0,
+            },
+            len: 0,
+        })
+        .collect();
+    let r = unsafe { recvmmsg(fd, msgs.as_mut_ptr(), n as c_uint, flags, std::ptr::null_mut()) };
+    for (len, msg) in lens.iter_mut().zip(&msgs[..r.max(0) as usize]) {
+        *len = msg.len as usize;
+    }
+    r
+}
+
+/// `sendmmsg` of each of `bufs` to the matching `dests` entry. Returns
+/// the number of datagrams sent (or -1).
+#[cfg(target_os = "linux")]
+pub(super) fn safe_sendmmsg(fd: c_int, bufs: &[&[u8]], dests: &[InetSockAddr], flags: c_int) -> i32 {
+    let n = bufs.len().min(dests.len());
+    let mut iovs: Vec<IoVec> = bufs[..n]
+        .iter()
+        .map(|b| IoVec {
+            base: b.as_ptr() as *mut c_void,
+            len: b.len() as c_ulong,
+        })
+        .collect();
+    let mut msgs: Vec<MMsgHdr> = iovs
+        .iter_mut()
+        .zip(dests[..n].iter())
+        .map(|(iov, dest)| MMsgHdr {
+            hdr: MsgHdr {
+                name: dest as *const InetSockAddr as *mut c_void,
+                namelen: size_of::<InetSockAddr>() as c_uint,
+                iov,
+                iovlen: 1,
+                control: std::ptr::null_mut(),
+                controllen: 0,
+                flags: 0,
+            },
+            len: 0,
+        })
+        .collect();
+    unsafe { sendmmsg(fd, msgs.as_mut_ptr(), n as c_uint, flags) }
+}
+
+/// Find the data of the first control message at `level`/`kind` in the
+/// ancillary buffer filled by `safe_recvmsg`.
+pub(super) fn find_cmsg(control: &[u8], level: c_int, kind: c_int) -> Option<&[u8]> {
+    let hdr = cmsg_align(size_of::<CmsgHdr>());
+    let mut off = 0;
+    while off + hdr <= control.len() {
+        let cm = unsafe { std::ptr::read_unaligned(control[off..].as_ptr() as *const CmsgHdr) };
+        let len = cm.len as usize;
+        if len < hdr || off + len > control.len() {
+            break;
+        }
+        if cm.level == level && cm.kind == kind {
+            return Some(&control[off + hdr..off + len]);
+        }
+        off += cmsg_align(len);
     }
+    None
+}
+
+/// An `ioctl` whose argument is a pointer to an int, such as the queue
+/// length requests.
+#[cfg(target_os = "linux")]
+pub(super) fn safe_ioctl_int(fd: c_int, request: c_ulong, val: &mut c_int) -> i32 {
+    unsafe { ioctl(fd, request, val as *mut c_int) }
+}
+
+/// `struct ifreq` as used by `SIOCGIFMTU`: the name, then `ifr_mtu` at the
+/// start of the 24-byte union.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct IfReqMtu {
+    name: [c_char; 16],
+    mtu: c_int,
+    pad: [u8; 20],
+}
+
+/// The MTU of the interface called `name` via `SIOCGIFMTU` on `fd`, which
+/// can be any socket. `name` must be shorter than 16 bytes. Returns the
+/// ioctl result and the MTU.
+#[cfg(target_os = "linux")]
+pub(super) fn safe_ioctl_ifmtu(fd: c_int, request: c_ulong, name: &CStr) -> (i32, c_int) {
+    let mut req = IfReqMtu {
+        name: [0; 16],
+        mtu: 0,
+        pad: [0; 20],
+    };
+    for (d, &b) in req.name.iter_mut().zip(name.to_bytes().iter().take(15)) {
+        *d = b as c_char;
+    }
+    let r = unsafe { ioctl(fd, request, &mut req as *mut IfReqMtu) };
+    (r, req.mtu)
+}
+
+/// Install `handler` for `sig`, returning the previous one (or `SIG_ERR`).
+/// Handlers are passed around as plain addresses.
+pub(super) fn safe_signal(sig: c_int, handler: usize) -> usize {
+    unsafe { signal(sig, handler) }
+}
+
+/// Close `fd` exactly once.
+///
+/// `close` can fail with `EINTR`, and POSIX leaves open whether the fd was
+/// released. On Linux, Android, the BSDs and macOS it always is: the
+/// descriptor is gone before the interruption is reported. Retrying would
+/// then close whatever another thread has since been given that number.
+/// The only well-known system that keeps the fd open on `EINTR` is HP-UX,
+/// which Rust has no target for, so there is no retrying path. Any error
+/// is ignored: the fd is released either way and there is nothing useful
+/// a caller (least of all `Drop`) could do about it.
+pub(super) fn safe_close(fd: c_int) {
+    let _ = unsafe { close(fd) };
 }
@@ -0,0 +1,71 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{RawFd, Socket, SocketError};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+
+/// A cheaply clonable handle to one socket shared between threads.
+///
+/// Every clone refers to the same descriptor, unlike `Socket::into_split`,
+/// which hands out independent `dup`s. The descriptor is closed when the
+/// last handle is dropped.
+///
+/// Nothing is serialized between handles. Concurrent reads each take
+/// whatever bytes arrive next, so one logical message can end up spread
+/// over several readers, and concurrent writes of more than one `write`
+/// call can interleave on the wire. Sharing works best for datagram
+/// sockets or when each side of the traffic has a single owner.
+#[derive(Clone)]
+pub struct SharedSocket(Arc<Socket>);
+
+impl SharedSocket {
+    pub fn new(sock: Socket) -> Self {
+        Self(Arc::new(sock))
+    }
+
+    pub fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        self.0.read(buffer)
+    }
+
+    pub fn write(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        self.0.write(buffer)
+    }
+
+    pub fn peek(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        self.0.peek(buffer)
+    }
+}
+
+impl From<Socket> for SharedSocket {
+    fn from(sock: Socket) -> Self {
+        Self::new(sock)
+    }
+}
+
+impl AsRawFd for SharedSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
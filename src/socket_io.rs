@@ -0,0 +1,146 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{Shutdown, Socket, SocketError};
+
+/// The byte-stream operations of a `Socket`, so code layered on top can be
+/// generic over them and run against a `MockSocket` in tests.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{Socket, SocketIo, SocketType};
+///
+/// fn greet<S: SocketIo>(conn: &S) -> Result<(), mzsocket::SocketError> {
+///     conn.write(b"hello\n")?;
+///     Ok(())
+/// }
+///
+/// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+/// greet(&client).unwrap();
+/// let mut buf = [0u8; 16];
+/// assert_eq!(SocketIo::read(&server, &mut buf).unwrap(), 6);
+/// ```
+pub trait SocketIo {
+    fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError>;
+
+    fn write(&self, buffer: &[u8]) -> Result<i64, SocketError>;
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), SocketError>;
+}
+
+impl SocketIo for Socket {
+    fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        Socket::read(self, buffer)
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        Socket::write(self, buffer)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), SocketError> {
+        Socket::shutdown(self, how)
+    }
+}
+
+/// An in-memory `SocketIo` for tests. Reads are served from data queued
+/// with `push_input`, and an empty queue reads as end of file. Whatever is
+/// written is kept for `written`. After a shutdown, reads on that side
+/// return 0 and writes fail with `EPIPE`, as on a real socket. Only
+/// available with the `test-util` feature.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{MockSocket, Shutdown, SocketIo};
+///
+/// let mock = MockSocket::new();
+/// mock.push_input(b"ping");
+/// let mut buf = [0u8; 8];
+/// assert_eq!(mock.read(&mut buf).unwrap(), 4);
+/// assert_eq!(mock.read(&mut buf).unwrap(), 0);
+///
+/// mock.write(b"pong").unwrap();
+/// assert_eq!(mock.written(), b"pong");
+/// mock.shutdown(Shutdown::Write).unwrap();
+/// assert!(mock.write(b"late").is_err());
+/// ```
+#[cfg(feature = "test-util")]
+#[derive(Debug, Default)]
+pub struct MockSocket {
+    input: std::cell::RefCell<std::collections::VecDeque<u8>>,
+    output: std::cell::RefCell<Vec<u8>>,
+    read_shut: std::cell::Cell<bool>,
+    write_shut: std::cell::Cell<bool>,
+}
+
+#[cfg(feature = "test-util")]
+impl MockSocket {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `data` to be returned by later reads.
+    pub fn push_input(&self, data: &[u8]) {
+        self.input.borrow_mut().extend(data);
+    }
+
+    /// Everything written so far.
+    pub fn written(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl SocketIo for MockSocket {
+    fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        if self.read_shut.get() {
+            return Ok(0);
+        }
+        let mut input = self.input.borrow_mut();
+        let n = buffer.len().min(input.len());
+        for (dst, src) in buffer.iter_mut().zip(input.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n as i64)
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        if self.write_shut.get() {
+            return Err(SocketError::Other(super::error::EPIPE));
+        }
+        self.output.borrow_mut().extend_from_slice(buffer);
+        Ok(buffer.len() as i64)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), SocketError> {
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            self.read_shut.set(true);
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.write_shut.set(true);
+        }
+        Ok(())
+    }
+}
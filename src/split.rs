@@ -0,0 +1,82 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{RawFd, Shutdown, Socket, SocketError};
+use std::os::fd::AsRawFd;
+
+/// The receiving half of a socket returned by `Socket::into_split`.
+pub struct ReadHalf {
+    sock: Socket,
+}
+
+impl ReadHalf {
+    pub(super) fn new(sock: Socket) -> Self {
+        Self { sock }
+    }
+
+    pub fn read(&self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        self.sock.read(buffer)
+    }
+
+    pub fn read_exact(&self, buffer: &mut [u8]) -> Result<(), SocketError> {
+        self.sock.read_exact(buffer)
+    }
+}
+
+/// The sending half of a socket returned by `Socket::into_split`.
+pub struct WriteHalf {
+    sock: Socket,
+}
+
+impl WriteHalf {
+    pub(super) fn new(sock: Socket) -> Self {
+        Self { sock }
+    }
+
+    pub fn write(&self, buffer: &[u8]) -> Result<i64, SocketError> {
+        self.sock.write(buffer)
+    }
+
+    pub fn write_all(&self, buffer: &[u8]) -> Result<(), SocketError> {
+        self.sock.write_all(buffer)
+    }
+
+    /// Shut down the connection. Note that this acts on the socket itself,
+    /// so shutting down `Read` here is also seen by the matching `ReadHalf`.
+    pub fn shutdown(&self, how: Shutdown) -> Result<(), SocketError> {
+        self.sock.shutdown(how)
+    }
+}
+
+impl AsRawFd for ReadHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+impl AsRawFd for WriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
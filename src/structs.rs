@@ -24,47 +24,302 @@
 
 #[repr(C)]
 #[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum BindFamily {
     Unix(String),
     Inet(u32, u16),
     Inet6(u128, u16),
+    /// An IPv6 address, port and scope (zone) id. Link-local addresses
+    /// (`fe80::/10`) need the scope id to pick the interface they live on.
+    Inet6Scoped(u128, u16, u32),
+    /// An IPv6 address, port, scope id and flow label. Only the low 20
+    /// bits of the label are used; it goes out in `sin6_flowinfo`.
+    Inet6Flow(u128, u16, u32, u32),
+}
+
+impl BindFamily {
+    /// The address family a socket needs to bind or connect to this.
+    pub const fn family(&self) -> AddressFamily {
+        match self {
+            Self::Unix(_) => AddressFamily::Unix,
+            Self::Inet(..) => AddressFamily::Inet,
+            Self::Inet6(..) | Self::Inet6Scoped(..) | Self::Inet6Flow(..) => AddressFamily::Inet6,
+        }
+    }
 }
 
-#[repr(C)]
 #[allow(dead_code)]
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddressFamily {
-    Unspec = 0,
-    Unix = 1,
-    Inet = 2,
-    Inet6 = 10,
+    Unspec,
+    Unix,
+    Inet,
+    Inet6,
+    /// Link-layer packet sockets (Linux `AF_PACKET`).
+    Packet,
+    /// A family the crate does not know by name, carrying the raw value.
+    Unknown(i32),
+}
+
+impl AddressFamily {
+    pub const fn from_raw(val: i32) -> Self {
+        match val {
+            0 => Self::Unspec,
+            1 => Self::Unix,
+            2 => Self::Inet,
+            10 => Self::Inet6,
+            17 => Self::Packet,
+            v => Self::Unknown(v),
+        }
+    }
+
+    pub const fn as_raw(self) -> i32 {
+        match self {
+            Self::Unspec => 0,
+            Self::Unix => 1,
+            Self::Inet => 2,
+            Self::Inet6 => 10,
+            Self::Packet => 17,
+            Self::Unknown(v) => v,
+        }
+    }
 }
 
-#[repr(C)]
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SocketType {
-    Stream = 1,
-    DataGram = 2,
-    Raw = 3,
-    SeqPacket = 5,
-    Packet = 10,
+    Stream,
+    DataGram,
+    Raw,
+    SeqPacket,
+    Packet,
+    /// A socket type the crate does not know by name, carrying the raw value.
+    Unknown(i32),
 }
 
-#[repr(C)]
+impl SocketType {
+    pub const fn from_raw(val: i32) -> Self {
+        match val {
+            1 => Self::Stream,
+            2 => Self::DataGram,
+            3 => Self::Raw,
+            5 => Self::SeqPacket,
+            10 => Self::Packet,
+            v => Self::Unknown(v),
+        }
+    }
+
+    pub const fn as_raw(self) -> i32 {
+        match self {
+            Self::Stream => 1,
+            Self::DataGram => 2,
+            Self::Raw => 3,
+            Self::SeqPacket => 5,
+            Self::Packet => 10,
+            Self::Unknown(v) => v,
+        }
+    }
+}
+
+/// The protocol argument to `socket`.
+///
+/// Protocols without a name here can still be used through `Unknown`,
+/// whose number is handed to `socket` unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{AddressFamily, IpProto, Socket, SocketError, SocketType};
+///
+/// // A raw socket for OSPF, IP protocol 89.
+/// let ospf = IpProto::Unknown(89);
+/// assert_eq!(IpProto::from_raw(89), ospf);
+/// match Socket::new(AddressFamily::Inet, SocketType::Raw, Some(ospf)) {
+///     Ok(sock) => assert_eq!(sock.protocol().unwrap(), ospf),
+///     // Raw sockets need CAP_NET_RAW.
+///     Err(SocketError::PermissionDenied) => {}
+///     Err(e) => panic!("{}", e),
+/// }
+/// ```
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum IpProto {
-    Ip = 0,
-    Icmp = 1,
-    Igmp = 2,
-    IpIp = 4,
-    Tcp = 6,
-    Udp = 17,
-    Ipv6 = 41,
-    Gre = 47,
-    Esp = 50,
-    Ah = 51,
+    Ip,
+    Icmp,
+    Igmp,
+    IpIp,
+    Tcp,
+    Udp,
+    Ipv6,
+    Gre,
+    Esp,
+    Ah,
+    Icmpv6,
+    Sctp,
+    /// Any other protocol number, e.g. 89 for OSPF. `from_raw` only
+    /// produces this for numbers without a named variant.
+    Unknown(i32),
+}
+
+impl IpProto {
+    pub const fn from_raw(val: i32) -> Self {
+        match val {
+            0 => Self::Ip,
+            1 => Self::Icmp,
+            2 => Self::Igmp,
+            4 => Self::IpIp,
+            6 => Self::Tcp,
+            17 => Self::Udp,
+            41 => Self::Ipv6,
+            47 => Self::Gre,
+            50 => Self::Esp,
+            51 => Self::Ah,
+            58 => Self::Icmpv6,
+            132 => Self::Sctp,
+            v => Self::Unknown(v),
+        }
+    }
+
+    pub const fn as_raw(self) -> i32 {
+        match self {
+            Self::Ip => 0,
+            Self::Icmp => 1,
+            Self::Igmp => 2,
+            Self::IpIp => 4,
+            Self::Tcp => 6,
+            Self::Udp => 17,
+            Self::Ipv6 => 41,
+            Self::Gre => 47,
+            Self::Esp => 50,
+            Self::Ah => 51,
+            Self::Icmpv6 => 58,
+            Self::Sctp => 132,
+            Self::Unknown(v) => v,
+        }
+    }
+}
+
+/// Flags for `send` and `recv`-style calls. Combine them with `|`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MsgFlags(pub i32);
+
+impl MsgFlags {
+    pub const NONE: Self = Self(0);
+    pub const OOB: Self = Self(0x1);
+    pub const PEEK: Self = Self(0x2);
+    /// Report the real length of a datagram even if it didn't fit.
+    pub const TRUNC: Self = Self(0x20);
+    pub const DONTWAIT: Self = Self(0x40);
+    pub const WAITALL: Self = Self(0x100);
+    pub const NOSIGNAL: Self = Self(0x4000);
+    /// More data follows, so don't push this out yet (Linux only).
+    #[cfg(target_os = "linux")]
+    pub const MORE: Self = Self(0x8000);
+    /// Connect and send in one step with TCP Fast Open (Linux only).
+    #[cfg(target_os = "linux")]
+    pub const FASTOPEN: Self = Self(0x2000_0000);
+    /// Read from the socket's error queue instead of its data (Linux only).
+    #[cfg(target_os = "linux")]
+    pub const ERRQUEUE: Self = Self(0x2000);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for MsgFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for MsgFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Options `Socket::accept_configured` applies to each accepted
+/// connection. The default changes nothing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcceptConfig {
+    /// Disable Nagle's algorithm (`TCP_NODELAY`).
+    pub nodelay: bool,
+    pub read_timeout: Option<std::time::Duration>,
+    pub write_timeout: Option<std::time::Duration>,
+    /// Turn on TCP keepalive, probing after this much idle time.
+    pub keepalive: Option<std::time::Duration>,
+}
+
+/// Flags `Socket::accept_with` sets on the accepted socket, atomically
+/// where `accept4` is available.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcceptFlags(pub i32);
+
+impl AcceptFlags {
+    pub const NONE: Self = Self(0);
+    /// `SOCK_NONBLOCK`
+    pub const NONBLOCK: Self = Self(0o4000);
+    /// `SOCK_CLOEXEC`
+    pub const CLOEXEC: Self = Self(0o2000000);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for AcceptFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The commonly tuned options of a socket, as read by
+/// `Socket::options_snapshot` and set by `Socket::apply`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// `SO_REUSEADDR`.
+    pub reuse_addr: bool,
+    /// `TCP_NODELAY`, or `None` on sockets that aren't TCP.
+    pub nodelay: Option<bool>,
+    /// `SO_KEEPALIVE`.
+    pub keepalive: bool,
+    /// `SO_RCVBUF` as reported by the kernel, which doubles what was set.
+    pub recv_buffer: i32,
+    /// `SO_SNDBUF` as reported by the kernel, which doubles what was set.
+    pub send_buffer: i32,
+    pub read_timeout: Option<std::time::Duration>,
+    pub write_timeout: Option<std::time::Duration>,
+    /// `IP_TTL`, or the unicast hop limit on IPv6; `None` on sockets that
+    /// aren't IP.
+    pub ttl: Option<u32>,
+}
+
+/// Progress of a non-blocking connect.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectState {
+    InProgress,
+    Connected,
+}
+
+/// Which direction(s) of a connection to shut down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shutdown {
+    Read,
+    Write,
+    Both,
+}
+
+impl Shutdown {
+    pub const fn as_raw(self) -> i32 {
+        match self {
+            Self::Read => 0,
+            Self::Write => 1,
+            Self::Both => 2,
+        }
+    }
 }
 
 pub union InetAddr {
@@ -92,6 +347,29 @@ impl InetAddr {
     pub const fn new(addr32: u32) -> Self {
         Self::new_32(addr32)
     }
+
+    /// The address as a host-order `u32`, the form `inet_addr` returns,
+    /// so 127.0.0.1 is `0x7f00_0001` on any machine.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet_addr, InetAddr};
+    ///
+    /// let addr = InetAddr::new_8([192, 0, 2, 1]);
+    /// assert_eq!(addr.bytes_to_u32(), 0xc000_0201);
+    /// assert_eq!(addr.bytes_to_u32(), inet_addr("192.0.2.1").unwrap());
+    /// assert_eq!(InetAddr::new(0xc000_0201u32.to_be()).u32_to_bytes(), [192, 0, 2, 1]);
+    /// ```
+    pub const fn bytes_to_u32(&self) -> u32 {
+        u32::from_be_bytes(self.u32_to_bytes())
+    }
+
+    /// The four octets in network order, as written in `a.b.c.d`.
+    pub const fn u32_to_bytes(&self) -> [u8; 4] {
+        // Every field covers all four bytes, so any read is initialized.
+        unsafe { self.addr8 }
+    }
 }
 
 impl Default for InetAddr {
@@ -109,10 +387,137 @@ pub struct InetSockAddr {
     pub addr: InetAddr,
     pub reserved: u64,
 }
+// The address is a union, so these can't be derived. Addresses compare by
+// family, then address bytes, then port. The padding in `reserved` is
+// ignored.
+impl InetSockAddr {
+    fn key(&self) -> (u16, [u8; 4], u16) {
+        (self.family, unsafe { self.addr.addr8 }, u16::from_be(self.port))
+    }
+}
+
+/// `fmt::Write` into a fixed byte buffer, failing once it is full.
+struct ByteWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> ByteWriter<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> std::fmt::Result {
+        let end = self.len + bytes.len();
+        if end > self.buf.len() {
+            return Err(std::fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl std::fmt::Write for ByteWriter<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.put(s.as_bytes())
+    }
+}
+
+/// Longest text `InetSockAddr::write_to` produces, `255.255.255.255:65535`.
+pub const INET_SOCKADDR_STRLEN: usize = 21;
+/// Longest text `Inet6SockAddr::write_to` produces: 39 characters of
+/// address, a `%` and 10-digit scope id, brackets, a colon and port.
+pub const INET6_SOCKADDR_STRLEN: usize = 58;
+
+impl InetSockAddr {
+    /// Parse a dotted quad with `inet_addr` and pair it with `port`, both
+    /// stored in network byte order. Errors are `inet_addr`'s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::InetSockAddr;
+    ///
+    /// let addr = InetSockAddr::from_str_port("192.0.2.10", 8080).unwrap();
+    /// assert_eq!(addr.to_string(), "192.0.2.10:8080");
+    /// assert_eq!(u16::from_be(addr.port), 8080);
+    /// assert_eq!(InetSockAddr::from_str_port("192.0.300.1", 80).err(), Some(2));
+    /// ```
+    pub fn from_str_port(addr: &str, port: u16) -> Result<Self, usize> {
+        Ok(Self {
+            port: port.to_be(),
+            addr: InetAddr::new(crate::inet_addr(addr)?.to_be()),
+            ..Self::default()
+        })
+    }
+
+    /// Write the address as `a.b.c.d:port` into `buf` without allocating,
+    /// returning the length written. Fails if `buf` is too short;
+    /// `INET_SOCKADDR_STRLEN` bytes always suffice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{InetSockAddr, INET_SOCKADDR_STRLEN};
+    ///
+    /// let mut addr = InetSockAddr::default();
+    /// addr.addr.addr32 = 0x7f00_0001u32.to_be();
+    /// addr.port = 8080u16.to_be();
+    /// let mut buf = [0u8; INET_SOCKADDR_STRLEN];
+    /// let len = addr.write_to(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], b"127.0.0.1:8080");
+    /// assert_eq!(addr.to_string(), "127.0.0.1:8080");
+    /// assert!(addr.write_to(&mut [0u8; 4]).is_err());
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        use std::fmt::Write;
+        let mut w = ByteWriter::new(buf);
+        let [a, b, c, d] = unsafe { self.addr.addr8 };
+        write!(w, "{}.{}.{}.{}:{}", a, b, c, d, u16::from_be(self.port)).map_err(|_| ())?;
+        Ok(w.len)
+    }
+}
+
+impl std::fmt::Display for InetSockAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = [0u8; INET_SOCKADDR_STRLEN];
+        let len = self.write_to(&mut buf).map_err(|_| std::fmt::Error)?;
+        f.write_str(std::str::from_utf8(&buf[..len]).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl PartialEq for InetSockAddr {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for InetSockAddr {}
+
+impl std::hash::Hash for InetSockAddr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl PartialOrd for InetSockAddr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InetSockAddr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 impl Default for InetSockAddr {
     fn default() -> Self {
         Self {
-            family: AddressFamily::Inet as u16,
+            family: AddressFamily::Inet.as_raw() as u16,
             port: 0,
             addr: InetAddr::new(0),
             reserved: 0
@@ -120,6 +525,9 @@ impl Default for InetSockAddr {
     }
 }
 
+// packed(4) keeps the u64/u128 members from raising the alignment, which
+// would otherwise move `addr` to offset 16 and break the sockaddr_in6 layout.
+#[repr(C, packed(4))]
 pub union Inet6Addr {
     pub addr8: [u8; 16],
     pub addr16: [u16; 8],
@@ -154,6 +562,22 @@ impl Inet6Addr {
             addr128
         }
     }
+
+    /// The eight 16-bit groups in host order, first group first, as
+    /// written in `2001:db8::1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet6_addr, Inet6Addr};
+    ///
+    /// let addr = Inet6Addr::new_8(inet6_addr("2001:db8::1").unwrap().to_be_bytes());
+    /// assert_eq!(addr.segments(), [0x2001, 0xdb8, 0, 0, 0, 0, 0, 1]);
+    /// ```
+    pub fn segments(&self) -> [u16; 8] {
+        let b = unsafe { self.addr8 };
+        std::array::from_fn(|i| u16::from_be_bytes([b[2 * i], b[2 * i + 1]]))
+    }
 }
 
 impl Default for Inet6Addr {
@@ -173,10 +597,160 @@ pub struct Inet6SockAddr {
     pub scopeid: u32,
 }
 
+/// Flow labels are the low 20 bits of `flowinfo`.
+pub const IPV6_FLOWLABEL_MASK: u32 = 0x000f_ffff;
+
+impl Inet6SockAddr {
+    /// The flow label carried in `flowinfo`, in host byte order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::Inet6SockAddr;
+    ///
+    /// let addr = Inet6SockAddr { flowinfo: 0x0012_3456_u32.to_be(), ..Default::default() };
+    /// assert_eq!(addr.flow_label(), 0x2_3456);
+    /// ```
+    pub fn flow_label(&self) -> u32 {
+        u32::from_be(self.flowinfo) & IPV6_FLOWLABEL_MASK
+    }
+}
+
+// As for InetSockAddr, with the scope id last so link-local addresses on
+// different interfaces stay distinct. `flowinfo` is ignored.
+impl Inet6SockAddr {
+    fn key(&self) -> (u16, [u8; 16], u16, u32) {
+        (self.family, unsafe { self.addr.addr8 }, u16::from_be(self.port), self.scopeid)
+    }
+}
+
+impl Inet6SockAddr {
+    /// Parse an IPv6 address with `inet6_addr_scoped`, so a `%zone`
+    /// suffix sets the scope id, and pair it with `port` in network byte
+    /// order. Errors are `inet6_addr_scoped`'s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::Inet6SockAddr;
+    ///
+    /// let addr = Inet6SockAddr::from_str_port("2001:db8::1", 443).unwrap();
+    /// assert_eq!(addr.to_string(), "[2001:db8::1]:443");
+    /// let scoped = Inet6SockAddr::from_str_port("fe80::1%7", 22).unwrap();
+    /// assert_eq!(scoped.scopeid, 7);
+    /// assert_eq!(Inet6SockAddr::from_str_port("2001:db8::g", 443).err(), Some(2));
+    /// ```
+    pub fn from_str_port(addr: &str, port: u16) -> Result<Self, usize> {
+        let (ip, scopeid) = crate::inet6_addr_scoped(addr)?;
+        let mut sa = Self {
+            port: port.to_be(),
+            scopeid,
+            ..Self::default()
+        };
+        sa.addr.addr8 = ip.to_be_bytes();
+        Ok(sa)
+    }
+
+    /// Write the address as `[addr]:port`, or `[addr%scope]:port` when it
+    /// has a scope id, into `buf` without allocating, returning the length
+    /// written. The address uses the RFC 5952 form: lowercase, with the
+    /// longest run of two or more zero groups shortened to `::`. Fails if
+    /// `buf` is too short; `INET6_SOCKADDR_STRLEN` bytes always suffice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{inet6_addr, Inet6SockAddr, INET6_SOCKADDR_STRLEN};
+    ///
+    /// let mut addr = Inet6SockAddr::default();
+    /// addr.addr.addr8 = inet6_addr("fe80::1:0:0:2").unwrap().to_be_bytes();
+    /// addr.port = 443u16.to_be();
+    /// addr.scopeid = 2;
+    /// let mut buf = [0u8; INET6_SOCKADDR_STRLEN];
+    /// let len = addr.write_to(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], b"[fe80::1:0:0:2%2]:443");
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        use std::fmt::Write;
+        let b = unsafe { self.addr.addr8 };
+        let groups: [u16; 8] = std::array::from_fn(|i| u16::from_be_bytes([b[2 * i], b[2 * i + 1]]));
+        // Longest run of zero groups, the first one on a tie.
+        let (mut run, mut best) = ((0, 0), (0, 0));
+        for (i, &g) in groups.iter().enumerate() {
+            if g == 0 {
+                run = if run.1 == 0 { (i, 1) } else { (run.0, run.1 + 1) };
+                if run.1 > best.1 {
+                    best = run;
+                }
+            } else {
+                run = (0, 0);
+            }
+        }
+        let mut w = ByteWriter::new(buf);
+        let mut fmt = || -> std::fmt::Result {
+            w.put(b"[")?;
+            let mut i = 0;
+            while i < 8 {
+                if best.1 >= 2 && i == best.0 {
+                    w.put(b"::")?;
+                    i += best.1;
+                    continue;
+                }
+                if i > 0 && !(best.1 >= 2 && i == best.0 + best.1) {
+                    w.put(b":")?;
+                }
+                write!(w, "{:x}", groups[i])?;
+                i += 1;
+            }
+            if self.scopeid != 0 {
+                write!(w, "%{}", self.scopeid)?;
+            }
+            write!(w, "]:{}", u16::from_be(self.port))
+        };
+        fmt().map_err(|_| ())?;
+        Ok(w.len)
+    }
+}
+
+impl std::fmt::Display for Inet6SockAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = [0u8; INET6_SOCKADDR_STRLEN];
+        let len = self.write_to(&mut buf).map_err(|_| std::fmt::Error)?;
+        f.write_str(std::str::from_utf8(&buf[..len]).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl PartialEq for Inet6SockAddr {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Inet6SockAddr {}
+
+impl std::hash::Hash for Inet6SockAddr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl PartialOrd for Inet6SockAddr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Inet6SockAddr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 impl Default for Inet6SockAddr {
     fn default() -> Self {
         Self {
-            family: AddressFamily::Inet6 as u16,
+            family: AddressFamily::Inet6.as_raw() as u16,
             port: 0,
             flowinfo: 0,
             addr: Inet6Addr::default(),
@@ -196,9 +770,494 @@ pub struct UnixSockAddr {
 impl Default for UnixSockAddr {
     fn default() -> Self {
         Self {
-            family: AddressFamily::Unix as u16,
+            family: AddressFamily::Unix.as_raw() as u16,
             path: [0u8; UNIX_PATH_LEN]
         }
     }
 }
 
+/// Shows the path, with any bytes that aren't UTF-8 replaced.
+impl std::fmt::Display for UnixSockAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = [0u8; UNIX_PATH_LEN];
+        let len = self.write_to(&mut buf).map_err(|_| std::fmt::Error)?;
+        for chunk in buf[..len].utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_str("\u{FFFD}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Only the path up to its terminator counts, not whatever follows it.
+impl PartialEq for UnixSockAddr {
+    fn eq(&self, other: &Self) -> bool {
+        self.family == other.family && self.path_bytes() == other.path_bytes()
+    }
+}
+
+impl Eq for UnixSockAddr {}
+
+impl std::hash::Hash for UnixSockAddr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.family.hash(state);
+        self.path_bytes().hash(state);
+    }
+}
+
+impl UnixSockAddr {
+    /// Whether this is a Linux abstract address: a leading NUL followed by
+    /// a name that lives outside the filesystem.
+    pub fn is_abstract(&self) -> bool {
+        self.path[0] == 0 && self.path[1..].iter().any(|&b| b != 0)
+    }
+
+    /// The raw path. For a filesystem path this stops at the NUL
+    /// terminator. For an abstract address it keeps the leading NUL and
+    /// runs to the last non-NUL byte, since the address length isn't
+    /// stored here. An unnamed socket gives an empty slice.
+    pub fn path_bytes(&self) -> &[u8] {
+        let end = if self.is_abstract() {
+            self.path.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1)
+        } else {
+            self.path.iter().position(|&b| b == 0).unwrap_or(UNIX_PATH_LEN)
+        };
+        &self.path[..end]
+    }
+
+    /// Write the path into `buf` without allocating, returning the length
+    /// written. An abstract address is written with an `@` in place of its
+    /// leading NUL, as tools like `ss` show it. Fails if `buf` is too
+    /// short; `UNIX_PATH_LEN` bytes always suffice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{UnixSockAddr, UNIX_PATH_LEN};
+    ///
+    /// let mut abs = UnixSockAddr::default();
+    /// abs.path[1..5].copy_from_slice(b"name");
+    /// let mut buf = [0u8; UNIX_PATH_LEN];
+    /// let len = abs.write_to(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], b"@name");
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, ()> {
+        let mut w = ByteWriter::new(buf);
+        let path = self.path_bytes();
+        let res = if self.is_abstract() {
+            w.put(b"@").and_then(|_| w.put(&path[1..]))
+        } else {
+            w.put(path)
+        };
+        res.map_err(|_| ())?;
+        Ok(w.len)
+    }
+
+    /// The filesystem path as a string, `""` for an unnamed socket, or
+    /// `None` for an abstract address or a path that isn't UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::UnixSockAddr;
+    ///
+    /// let mut addr = UnixSockAddr::default();
+    /// addr.path[..9].copy_from_slice(b"/tmp/sock");
+    /// assert_eq!(addr.path_str(), Some("/tmp/sock"));
+    ///
+    /// let mut abs = UnixSockAddr::default();
+    /// abs.path[1..5].copy_from_slice(b"name");
+    /// assert!(abs.is_abstract());
+    /// assert_eq!(abs.path_str(), None);
+    /// assert_eq!(abs.path_bytes(), b"\0name");
+    /// ```
+    pub fn path_str(&self) -> Option<&str> {
+        if self.is_abstract() {
+            return None;
+        }
+        std::str::from_utf8(self.path_bytes()).ok()
+    }
+}
+
+
+/// Interface flags (`IFF_*`) reported with each `InterfaceAddr`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IfFlags(pub u32);
+
+impl IfFlags {
+    pub const UP: Self = Self(0x1);
+    pub const BROADCAST: Self = Self(0x2);
+    pub const LOOPBACK: Self = Self(0x8);
+    pub const POINTOPOINT: Self = Self(0x10);
+    /// Up and with a working link.
+    pub const RUNNING: Self = Self(0x40);
+    pub const MULTICAST: Self = Self(0x1000);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for IfFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// One address of a local network interface, from `interface_addrs`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    /// Interface name, e.g. `"eth0"`.
+    pub name: String,
+    pub family: AddressFamily,
+    /// The address with port 0, ready to `bind`. IPv6 link-local
+    /// addresses come with their scope as `BindFamily::Inet6Scoped`.
+    pub addr: BindFamily,
+    pub flags: IfFlags,
+}
+
+/// A signal disposition returned by `ignore_sigpipe`, to hand back to
+/// `restore_sigpipe`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SigHandler(pub(crate) usize);
+
+impl SigHandler {
+    /// The default action, which for `SIGPIPE` terminates the process.
+    pub const fn is_default(self) -> bool {
+        self.0 == crate::consts::SIG_DFL
+    }
+
+    pub const fn is_ignored(self) -> bool {
+        self.0 == crate::consts::SIG_IGN
+    }
+}
+
+/// `struct ip_mreq_source` in Linux's field order, all in network byte
+/// order.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct IpMreqSource {
+    pub multiaddr: u32,
+    pub interface: u32,
+    pub sourceaddr: u32,
+}
+
+/// The process id, user id and group id of a Unix socket peer, as in
+/// Linux's `struct ucred`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerCred {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// `struct timeval`, used for the timeout socket options.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct TimeVal {
+    pub sec: i64,
+    pub usec: i64,
+}
+
+impl TimeVal {
+    /// Convert an optional timeout, where `None` becomes the all-zero
+    /// value the kernel reads as "no timeout".
+    pub fn from_duration(timeout: Option<std::time::Duration>) -> Self {
+        match timeout {
+            Some(d) => {
+                let mut tv = Self {
+                    sec: d.as_secs() as i64,
+                    usec: d.subsec_micros() as i64,
+                };
+                // Don't let a sub-microsecond remainder round down to "forever".
+                if tv.sec == 0 && tv.usec == 0 {
+                    tv.usec = 1;
+                }
+                tv
+            }
+            None => Self::default(),
+        }
+    }
+
+    /// The inverse of `from_duration`.
+    pub fn to_duration(self) -> Option<std::time::Duration> {
+        if self.sec == 0 && self.usec == 0 {
+            None
+        } else {
+            Some(std::time::Duration::new(self.sec as u64, self.usec as u32 * 1000))
+        }
+    }
+}
+
+/// Who a frame received on a packet socket was meant for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PacketType {
+    Host,
+    Broadcast,
+    Multicast,
+    /// Addressed to another host, seen because the interface is promiscuous.
+    OtherHost,
+    /// Sent by this host and looped back to the packet socket.
+    Outgoing,
+    Unknown(u8),
+}
+
+impl PacketType {
+    pub const fn from_raw(val: u8) -> Self {
+        match val {
+            0 => Self::Host,
+            1 => Self::Broadcast,
+            2 => Self::Multicast,
+            3 => Self::OtherHost,
+            4 => Self::Outgoing,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/// `struct sockaddr_ll`, the address of a link-layer packet socket.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LinkAddr {
+    pub family: u16,
+    /// Ethernet protocol, in network byte order.
+    pub protocol: u16,
+    /// Index of the interface the frame arrived on.
+    pub ifindex: i32,
+    /// ARP hardware type of that interface (`ARPHRD_*`).
+    pub hatype: u16,
+    pub pkttype: u8,
+    /// How many bytes of `addr` are used.
+    pub halen: u8,
+    pub addr: [u8; 8],
+}
+
+impl LinkAddr {
+    pub const fn packet_type(&self) -> PacketType {
+        PacketType::from_raw(self.pkttype)
+    }
+
+    /// The sender's hardware address, e.g. six bytes for Ethernet.
+    pub fn hardware_addr(&self) -> &[u8] {
+        &self.addr[..(self.halen as usize).min(self.addr.len())]
+    }
+}
+
+/// Where a queued socket error came from (`SO_EE_ORIGIN_*`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrOrigin {
+    None,
+    /// Raised by the local stack, e.g. a send larger than the path MTU.
+    Local,
+    /// An ICMP message from a router or the peer.
+    Icmp,
+    /// An ICMPv6 message from a router or the peer.
+    Icmp6,
+    Unknown(u8),
+}
+
+impl ErrOrigin {
+    pub const fn from_raw(val: u8) -> Self {
+        match val {
+            0 => Self::None,
+            1 => Self::Local,
+            2 => Self::Icmp,
+            3 => Self::Icmp6,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/// An error read from a socket's error queue (`struct sock_extended_err`).
+pub struct SockExtendedErr {
+    /// The errno the error maps to, e.g. `EHOSTUNREACH`.
+    pub errno: i32,
+    pub origin: ErrOrigin,
+    /// ICMP type, when `origin` is `Icmp` or `Icmp6`.
+    pub kind: u8,
+    /// ICMP code, when `origin` is `Icmp` or `Icmp6`.
+    pub code: u8,
+    /// Extra information, such as the path MTU for `EMSGSIZE`.
+    pub info: u32,
+    pub data: u32,
+    /// The host that reported the error, when the kernel knows it.
+    pub offender: Option<SockAddr>,
+}
+
+impl SockExtendedErr {
+    /// Parse the payload of an `IP_RECVERR`/`IPV6_RECVERR` control message:
+    /// the fixed 16-byte header followed by the offender's sockaddr.
+    pub(crate) fn from_cmsg(data: &[u8]) -> Option<Self> {
+        use std::mem::size_of;
+        use std::ptr::read_unaligned;
+        if data.len() < 16 {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let sa = &data[16..];
+        let offender = match crate::sockaddr_family(sa) {
+            Some(AddressFamily::Inet) if sa.len() >= size_of::<InetSockAddr>() => Some(SockAddr::Inet(unsafe {
+                read_unaligned(sa.as_ptr() as *const InetSockAddr)
+            })),
+            Some(AddressFamily::Inet6) if sa.len() >= size_of::<Inet6SockAddr>() => Some(SockAddr::Inet6(unsafe {
+                read_unaligned(sa.as_ptr() as *const Inet6SockAddr)
+            })),
+            _ => None,
+        };
+        Some(Self {
+            errno: u32_at(0) as i32,
+            origin: ErrOrigin::from_raw(data[4]),
+            kind: data[5],
+            code: data[6],
+            info: u32_at(8),
+            data: u32_at(12),
+            offender,
+        })
+    }
+}
+
+/// Connection statistics from `TCP_INFO` (Linux only).
+///
+/// The kernel's `struct tcp_info` has grown over the years, so it is
+/// parsed by the length the kernel returns: a field the running kernel
+/// doesn't provide is left at 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// Connection state, as the kernel's `TCP_*` numbers (1 is
+    /// `TCP_ESTABLISHED`).
+    pub state: u8,
+    /// Unrecovered retransmits of the current segment.
+    pub retransmits: u8,
+    /// Retransmission timeout, in microseconds.
+    pub rto: u32,
+    pub snd_mss: u32,
+    pub rcv_mss: u32,
+    /// Segments sent but not yet acknowledged.
+    pub unacked: u32,
+    pub lost: u32,
+    /// Smoothed round trip time, in microseconds.
+    pub rtt: u32,
+    /// Round trip time variance, in microseconds.
+    pub rttvar: u32,
+    pub snd_ssthresh: u32,
+    /// Congestion window, in segments.
+    pub snd_cwnd: u32,
+    /// Retransmits over the life of the connection.
+    pub total_retrans: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl TcpInfo {
+    pub(crate) fn from_bytes(data: &[u8]) -> Self {
+        let u8_at = |i: usize| data.get(i).copied().unwrap_or(0);
+        let u32_at = |i: usize| match data.get(i..i + 4) {
+            Some(b) => u32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            None => 0,
+        };
+        Self {
+            state: u8_at(0),
+            retransmits: u8_at(2),
+            rto: u32_at(8),
+            snd_mss: u32_at(16),
+            rcv_mss: u32_at(20),
+            unacked: u32_at(24),
+            lost: u32_at(32),
+            rtt: u32_at(68),
+            rttvar: u32_at(72),
+            snd_ssthresh: u32_at(76),
+            snd_cwnd: u32_at(80),
+            total_retrans: u32_at(100),
+        }
+    }
+}
+
+/// A socket address of any of the families the crate supports.
+///
+/// Addresses can be compared and hashed, so a peer address returned by
+/// `accept` can key a `HashMap`.
+///
+/// # Examples
+///
+/// ```
+/// use mzsocket::{InetSockAddr, SockAddr};
+/// use std::collections::HashMap;
+///
+/// let mut a = InetSockAddr::default();
+/// a.port = 8080u16.to_be();
+/// a.reserved = 0xdead; // padding, not part of the address
+/// let mut b = InetSockAddr::default();
+/// b.port = 8080u16.to_be();
+/// assert!(a == b);
+///
+/// let mut c = InetSockAddr::default();
+/// c.port = 80u16.to_be();
+/// assert!(c < b);
+///
+/// let mut sessions = HashMap::new();
+/// sessions.insert(SockAddr::Inet(a), "session 1");
+/// assert_eq!(sessions.get(&SockAddr::Inet(b)), Some(&"session 1"));
+/// ```
+#[derive(PartialEq, Eq, Hash)]
+pub enum SockAddr {
+    Inet(InetSockAddr),
+    Inet6(Inet6SockAddr),
+    Unix(UnixSockAddr),
+}
+
+impl SockAddr {
+    /// An empty address of the given family, or `EAFNOSUPPORT` for a family
+    /// with no sockaddr type here.
+    pub fn new(family: AddressFamily) -> Result<Self, crate::SocketError> {
+        match family {
+            AddressFamily::Inet => Ok(Self::Inet(InetSockAddr::default())),
+            AddressFamily::Inet6 => Ok(Self::Inet6(Inet6SockAddr::default())),
+            AddressFamily::Unix => Ok(Self::Unix(UnixSockAddr::default())),
+            _ => Err(crate::SocketError::Other(crate::error::EAFNOSUPPORT)),
+        }
+    }
+
+    pub fn family(&self) -> AddressFamily {
+        match self {
+            Self::Inet(_) => AddressFamily::Inet,
+            Self::Inet6(_) => AddressFamily::Inet6,
+            Self::Unix(_) => AddressFamily::Unix,
+        }
+    }
+
+    /// Pointer to the wrapped sockaddr and its size, for calls that fill
+    /// one in.
+    pub(crate) fn as_mut_raw(&mut self) -> (*mut std::ffi::c_void, u32) {
+        use std::mem::size_of;
+        match self {
+            Self::Inet(a) => (a as *mut InetSockAddr as *mut _, size_of::<InetSockAddr>() as u32),
+            Self::Inet6(a) => (a as *mut Inet6SockAddr as *mut _, size_of::<Inet6SockAddr>() as u32),
+            Self::Unix(a) => (a as *mut UnixSockAddr as *mut _, size_of::<UnixSockAddr>() as u32),
+        }
+    }
+
+    /// Whether a call that filled this in through `as_mut_raw` and
+    /// reported `slen` bytes left a usable address: at least the family's
+    /// minimum length, not truncated, and with the family field matching.
+    /// Unix peers may be unnamed, with only the family filled in.
+    pub(crate) fn is_valid_fill(&self, slen: u32) -> bool {
+        use std::mem::size_of;
+        let (min, max, raw_family) = match self {
+            Self::Inet(a) => (size_of::<InetSockAddr>(), size_of::<InetSockAddr>(), a.family),
+            Self::Inet6(a) => (size_of::<Inet6SockAddr>(), size_of::<Inet6SockAddr>(), a.family),
+            Self::Unix(a) => (size_of::<u16>(), size_of::<UnixSockAddr>(), a.family),
+        };
+        let slen = slen as usize;
+        slen >= min && slen <= max && raw_family == self.family().as_raw() as u16
+    }
+}
+
+impl Default for SockAddr {
+    fn default() -> Self {
+        Self::Inet(InetSockAddr::default())
+    }
+}
@@ -0,0 +1,120 @@
+//! mzsocket
+//! BSD socket wrapper using the standard C library
+//! Stephen Marz
+//! 27-Nov-2022
+
+//! Copyright (c) 2022 Stephen Marz
+//! Permission is hereby granted, free of charge, to any person obtaining a copy
+//! of this software and associated documentation files (the "Software"), to deal
+//! in the Software without restriction, including without limitation the rights
+//! to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//! copies of the Software, and to permit persons to whom the Software is
+//! furnished to do so, subject to the following conditions:
+
+//! The above copyright notice and this permission notice shall be included in
+//! all copies or substantial portions of the Software.
+
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//! IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//! FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL
+//! THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//! LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//! OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+//! THE SOFTWARE.
+
+use super::{RawFd, Socket, SocketError};
+use std::os::fd::AsRawFd;
+
+/// A socket that reads as EOF after a fixed number of bytes, returned by
+/// `Socket::take`.
+///
+/// This caps how much a peer can make us read, whether from a huge length
+/// prefix or from trickling data for ever. Once the limit is used up,
+/// `read` returns 0 just as it would for a peer that closed, so a length
+/// limited `read_to_end` needs nothing extra. Whether the peer had more to
+/// send can't be told from the data; check `limit()` (0 means the cap was
+/// hit, or exactly met) and treat the connection as hostile if that matters.
+pub struct TakeSocket {
+    sock: Socket,
+    limit: u64,
+}
+
+impl TakeSocket {
+    pub(super) fn new(sock: Socket, limit: u64) -> Self {
+        Self { sock, limit }
+    }
+
+    /// Read into `buffer`, never past the remaining limit. Returns 0 once
+    /// the limit is used up or the peer closed.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<i64, SocketError> {
+        if self.limit == 0 {
+            return Ok(0);
+        }
+        let max = buffer.len().min(self.limit.min(usize::MAX as u64) as usize);
+        let n = self.sock.read(&mut buffer[..max])?;
+        self.limit -= n as u64;
+        Ok(n)
+    }
+
+    /// Read until EOF or the limit, appending to `buf`. Returns the number
+    /// of bytes appended.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mzsocket::{Shutdown, Socket, SocketType};
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(b"short request").unwrap();
+    /// client.shutdown(Shutdown::Write).unwrap();
+    /// let mut limited = server.take(1024);
+    /// let mut buf = Vec::new();
+    /// assert_eq!(limited.read_to_end(&mut buf).unwrap(), 13);
+    /// assert_eq!(limited.limit(), 1011);
+    ///
+    /// let (client, server) = Socket::loopback_pair(SocketType::Stream).unwrap();
+    /// client.write_all(&[b'x'; 100]).unwrap();
+    /// let mut limited = server.take(10);
+    /// let mut buf = Vec::new();
+    /// // Stops at the cap without waiting for the peer to close.
+    /// assert_eq!(limited.read_to_end(&mut buf).unwrap(), 10);
+    /// assert_eq!(limited.limit(), 0);
+    /// ```
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, SocketError> {
+        let mut chunk = [0u8; 4096];
+        let start = buf.len();
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(buf.len() - start),
+                n => buf.extend_from_slice(&chunk[..n as usize]),
+            }
+        }
+    }
+
+    /// How many more bytes may be read.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Change how many more bytes may be read, for example per request on
+    /// a kept-alive connection.
+    pub fn set_limit(&mut self, limit: u64) {
+        self.limit = limit;
+    }
+
+    /// The underlying socket, for writing replies.
+    pub fn get_ref(&self) -> &Socket {
+        &self.sock
+    }
+
+    /// Give back the underlying socket, dropping the limit.
+    pub fn into_inner(self) -> Socket {
+        self.sock
+    }
+}
+
+impl AsRawFd for TakeSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}